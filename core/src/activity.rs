@@ -0,0 +1,359 @@
+//! Activity recording: distance, time, speed and pace for a walk, run or ride.
+//!
+//! Distance is added up from successive fixes. A stationary receiver's position still wanders by
+//! a few meters, which would add up to a lot over a long stop, so fixes only count while the
+//! receiver reports moving, and only once they're a few meters on from the last counted one.
+
+use core::fmt::Write;
+
+use arrayvec::ArrayString;
+
+use crate::geo::Position;
+use crate::settings::{crc32, Units};
+
+/// Speed over ground below which the receiver is taken to be stationary, in hundredths of a knot
+/// (about 0.5 m/s, well under walking pace)
+const MOVING_SPEED_CKNOTS: u32 = 100;
+/// How far on from the last counted fix a fix has to be to count, in meters
+const MIN_STEP_M: u32 = 5;
+/// Longest gap between fixes that still counts as moving time, in milliseconds, so losing the fix
+/// for a while doesn't add to it
+const MAX_FIX_GAP_MS: u64 = 10_000;
+/// Identifies a summary record ("ACTV")
+const MAGIC: u32 = 0x5654_4341;
+/// Size of a summary record in words: magic, summary and CRC
+pub const RECORD_WORDS: usize = 6;
+/// Millimeters in a kilometer and a mile
+const MM_PER_KM: u64 = 1_000_000;
+const MM_PER_MILE: u64 = 1_609_344;
+
+/// Whether an activity is being recorded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ActivityState {
+    Stopped,
+    Running,
+    Paused
+}
+
+/// Totals for a finished activity
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ActivitySummary {
+    pub distance_m: u32,
+    /// Time from start to stop, less time spent paused
+    pub elapsed_s: u32,
+    /// Time spent moving, which is what the average speed is over
+    pub moving_s: u32,
+    /// When it was stopped, as a Unix timestamp
+    pub ended: u32
+}
+
+impl ActivitySummary {
+    /// Average speed while moving, in millimeters per second
+    pub fn average_speed_mm_s(&self) -> u32 {
+        average_speed_mm_s(self.distance_m, self.moving_s as u64 * 1000)
+    }
+
+    pub fn to_record(self) -> [u32; RECORD_WORDS] {
+        let mut record = [MAGIC, self.distance_m, self.elapsed_s, self.moving_s, self.ended, 0];
+        record[RECORD_WORDS - 1] = crc32(&record[..RECORD_WORDS - 1]);
+        record
+    }
+
+    /// Deserialize a record, or `None` if there isn't a valid one
+    pub fn from_record(record: &[u32; RECORD_WORDS]) -> Option<Self> {
+        if record[0] != MAGIC || crc32(&record[..RECORD_WORDS - 1]) != record[RECORD_WORDS - 1] {
+            return None;
+        }
+        Some(Self {
+            distance_m: record[1],
+            elapsed_s: record[2],
+            moving_s: record[3],
+            ended: record[4]
+        })
+    }
+}
+
+/// The activity being recorded. Times are milliseconds from any fixed point, e.g. boot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    state: ActivityState,
+    distance_m: u32,
+    /// Elapsed time before the current run of not being paused
+    elapsed_ms: u64,
+    /// When it was last started or resumed, while running
+    running_since: Option<u64>,
+    moving_ms: u64,
+    /// Last fix that was counted towards the distance
+    anchor: Option<Position>,
+    /// When the last fix was taken in, while running
+    last_fix_ms: Option<u64>,
+    /// Current speed in millimeters per second, 0 if stationary
+    speed_mm_s: u32
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            state: ActivityState::Stopped,
+            distance_m: 0,
+            elapsed_ms: 0,
+            running_since: None,
+            moving_ms: 0,
+            anchor: None,
+            last_fix_ms: None,
+            speed_mm_s: 0
+        }
+    }
+}
+
+impl Activity {
+    pub fn state(&self) -> ActivityState {
+        self.state
+    }
+
+    /// Start a new activity, throwing away the current one
+    pub fn start(&mut self, now_ms: u64) {
+        *self = Self {
+            state: ActivityState::Running,
+            running_since: Some(now_ms),
+            ..Self::default()
+        };
+    }
+
+    pub fn pause(&mut self, now_ms: u64) {
+        if let Some(since) = self.running_since.take() {
+            self.elapsed_ms += now_ms.saturating_sub(since);
+            self.state = ActivityState::Paused;
+            // Don't count the gap when resuming
+            self.last_fix_ms = None;
+            self.speed_mm_s = 0;
+        }
+    }
+
+    pub fn resume(&mut self, now_ms: u64) {
+        if self.state == ActivityState::Paused {
+            self.state = ActivityState::Running;
+            self.running_since = Some(now_ms);
+        }
+    }
+
+    /// Stop the activity, returning its summary if there was one going. `ended` is the time as a
+    /// Unix timestamp.
+    pub fn stop(&mut self, now_ms: u64, ended: u32) -> Option<ActivitySummary> {
+        if self.state == ActivityState::Stopped {
+            return None;
+        }
+        self.pause(now_ms);
+        self.state = ActivityState::Stopped;
+        Some(ActivitySummary {
+            distance_m: self.distance_m,
+            elapsed_s: (self.elapsed_ms / 1000) as u32,
+            moving_s: (self.moving_ms / 1000) as u32,
+            ended
+        })
+    }
+
+    /// Take in a valid fix, with the speed over ground if it was reported
+    pub fn fix(&mut self, position: Position, speed_cknots: Option<u32>, now_ms: u64) {
+        if self.state != ActivityState::Running {
+            return;
+        }
+        let gap = self.last_fix_ms.map(|last| now_ms.saturating_sub(last));
+        self.last_fix_ms = Some(now_ms);
+
+        let moving = speed_cknots.filter(|&s| s >= MOVING_SPEED_CKNOTS);
+        // 1 knot is 1852 m/h
+        self.speed_mm_s = moving.map_or(0, |s| (s as u64 * 1852 / 360) as u32);
+        if moving.is_none() {
+            return;
+        }
+        if let Some(gap) = gap.filter(|&g| g <= MAX_FIX_GAP_MS) {
+            self.moving_ms += gap;
+        }
+        match self.anchor {
+            Some(anchor) => {
+                let step = anchor.distance_m(&position);
+                if step >= MIN_STEP_M {
+                    self.distance_m = self.distance_m.saturating_add(step);
+                    self.anchor = Some(position);
+                }
+            }
+            None => self.anchor = Some(position)
+        }
+    }
+
+    pub fn distance_m(&self) -> u32 {
+        self.distance_m
+    }
+
+    /// Time since starting, less time spent paused
+    pub fn elapsed_ms(&self, now_ms: u64) -> u64 {
+        self.elapsed_ms + self.running_since.map_or(0, |since| now_ms.saturating_sub(since))
+    }
+
+    pub fn moving_ms(&self) -> u64 {
+        self.moving_ms
+    }
+
+    /// Current speed in millimeters per second, 0 if stationary
+    pub fn speed_mm_s(&self) -> u32 {
+        self.speed_mm_s
+    }
+
+    /// Average speed while moving, in millimeters per second
+    pub fn average_speed_mm_s(&self) -> u32 {
+        average_speed_mm_s(self.distance_m, self.moving_ms)
+    }
+}
+
+fn average_speed_mm_s(distance_m: u32, moving_ms: u64) -> u32 {
+    if moving_ms == 0 {
+        return 0;
+    }
+    (distance_m as u64 * 1_000_000 / moving_ms).min(u32::MAX as u64) as u32
+}
+
+/// Format a duration as "h:mm:ss", or "m:ss" if it's under an hour
+pub fn format_duration(seconds: u64) -> ArrayString<12> {
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    // At most 16 digits before the first colon, so this can't fail
+    let mut text = ArrayString::new();
+    let _ = if h > 0 {
+        write!(text, "{}:{:02}:{:02}", h.min(99_999), m, s)
+    }
+    else {
+        write!(text, "{}:{:02}", m, s)
+    };
+    text
+}
+
+/// Format a speed in millimeters per second as e.g. "10.8 km/h" or "6.7 mph"
+pub fn format_speed(mm_s: u32, units: Units) -> ArrayString<12> {
+    let per_unit = match units {
+        Units::Metric => MM_PER_KM,
+        Units::Imperial => MM_PER_MILE
+    };
+    // Tenths of a unit per hour, rounded
+    let tenths = (mm_s as u64 * 36_000 + per_unit / 2) / per_unit;
+    let mut text = ArrayString::new();
+    let _ = write!(text, "{}.{} {}", tenths / 10, tenths % 10, match units {
+        Units::Metric => "km/h",
+        Units::Imperial => "mph"
+    });
+    text
+}
+
+/// Format a speed in millimeters per second as the time to cover a kilometer or mile, e.g.
+/// "5:32 /km". Dashes if that would take over 100 minutes, i.e. stationary.
+pub fn format_pace(mm_s: u32, units: Units) -> ArrayString<12> {
+    let (per_unit, unit) = match units {
+        Units::Metric => (MM_PER_KM, "/km"),
+        Units::Imperial => (MM_PER_MILE, "/mi")
+    };
+    let mut text = ArrayString::new();
+    let seconds = (per_unit + mm_s as u64 / 2).checked_div(mm_s as u64).filter(|&s| s < 100 * 60);
+    let _ = match seconds {
+        Some(s) => write!(text, "{}:{:02} {}", s / 60, s % 60, unit),
+        None => write!(text, "--:-- {}", unit)
+    };
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// About 11 cm per step north, near the equator
+    fn pos(y: i32) -> Position {
+        Position { latitude: y, longitude: 0 }
+    }
+
+    /// 5 knots, about 2.6 m/s
+    const SPEED: Option<u32> = Some(500);
+
+    #[test]
+    fn distance_and_time() {
+        let mut activity = Activity::default();
+        activity.fix(pos(0), SPEED, 0);
+        assert_eq!(activity.distance_m(), 0, "counted while stopped");
+
+        activity.start(1000);
+        // 10 m north every second for a minute
+        for i in 0..=60 {
+            activity.fix(pos(i * 90), SPEED, 1000 + i as u64 * 1000);
+        }
+        assert!((activity.distance_m() as i32 - 600).abs() < 5, "{}", activity.distance_m());
+        assert_eq!(activity.moving_ms(), 60_000);
+        assert_eq!(activity.elapsed_ms(61_000), 60_000);
+        assert_eq!(activity.speed_mm_s(), 2572);
+        assert_eq!(activity.average_speed_mm_s() / 100, 100);
+    }
+
+    #[test]
+    fn stationary_jitter_ignored() {
+        let mut activity = Activity::default();
+        activity.start(0);
+        for i in 0..100 {
+            // Wandering 10 m back and forth, but reported as stationary
+            activity.fix(pos((i % 2) * 90), Some(20), i as u64 * 1000);
+        }
+        assert_eq!(activity.distance_m(), 0);
+        assert_eq!(activity.moving_ms(), 0);
+        assert_eq!(activity.elapsed_ms(100_000), 100_000);
+
+        // 1 m steps only count once they add up to 5 m, so the last 4 m aren't counted yet
+        for i in 0..10 {
+            activity.fix(pos(i * 9), SPEED, 100_000 + i as u64 * 1000);
+        }
+        assert_eq!(activity.distance_m(), 5);
+    }
+
+    #[test]
+    fn pause_resume_stop() {
+        let mut activity = Activity::default();
+        assert_eq!(activity.stop(0, 0), None);
+
+        activity.start(0);
+        activity.fix(pos(0), SPEED, 0);
+        activity.fix(pos(900), SPEED, 10_000);
+        activity.pause(20_000);
+        assert_eq!(activity.state(), ActivityState::Paused);
+        // Fixes while paused don't count, and the gap isn't moving time
+        activity.fix(pos(9000), SPEED, 30_000);
+        activity.resume(50_000);
+        activity.fix(pos(9000), SPEED, 55_000);
+        assert_eq!(activity.moving_ms(), 10_000);
+
+        let summary = activity.stop(60_000, 1_700_000_000).unwrap();
+        assert_eq!(summary.elapsed_s, 30);
+        assert_eq!(summary.moving_s, 10);
+        assert!(summary.distance_m > 900, "{}", summary.distance_m);
+        assert_eq!(activity.state(), ActivityState::Stopped);
+        assert_eq!(activity.elapsed_ms(100_000), 30_000);
+    }
+
+    #[test]
+    fn summary_round_trip() {
+        let summary = ActivitySummary { distance_m: 12_345, elapsed_s: 3_600, moving_s: 3_000, ended: 1_700_000_000 };
+        assert_eq!(ActivitySummary::from_record(&summary.to_record()), Some(summary));
+        assert_eq!(ActivitySummary::from_record(&[0; RECORD_WORDS]), None);
+        let mut corrupt = summary.to_record();
+        corrupt[1] ^= 1;
+        assert_eq!(ActivitySummary::from_record(&corrupt), None);
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(&format_duration(0), "0:00");
+        assert_eq!(&format_duration(59 * 60 + 59), "59:59");
+        assert_eq!(&format_duration(3600 + 2 * 60 + 3), "1:02:03");
+
+        // 3 m/s
+        assert_eq!(&format_speed(3000, Units::Metric), "10.8 km/h");
+        assert_eq!(&format_speed(3000, Units::Imperial), "6.7 mph");
+        assert_eq!(&format_pace(3000, Units::Metric), "5:33 /km");
+        assert_eq!(&format_pace(3000, Units::Imperial), "8:56 /mi");
+        assert_eq!(&format_pace(0, Units::Metric), "--:-- /km");
+        assert_eq!(&format_pace(100, Units::Metric), "--:-- /km");
+    }
+}
@@ -0,0 +1,150 @@
+//! Working out the battery voltage, charge and level from ADC readings.
+//!
+//! The battery is measured on a pin through a 1:1 resistor divider. Since the MCU's supply (and so
+//! the ADC reference) is regulated down from the battery, VREFINT is measured too, and its factory
+//! calibration value is used to work out the actual supply voltage.
+
+/// Battery voltage is divided by this before reaching the pin
+const DIVIDER: u32 = 2;
+/// Supply voltage VREFINT_CAL was measured at, in millivolts
+const VREFINT_CAL_VDDA_MV: u32 = 3000;
+/// Full scale ADC reading (12 bits)
+const ADC_MAX: u32 = 4095;
+
+/// Below this, the battery is low
+const LOW_MV: u16 = 3600;
+/// Below this, the battery is critically low
+const CRITICAL_MV: u16 = 3400;
+/// How far above a threshold the voltage needs to go before the level goes back up, so the
+/// level doesn't flap when the voltage sags under load
+const HYSTERESIS_MV: u16 = 100;
+
+/// Typical single-cell Li-ion discharge curve at low load: (millivolts, percent), highest first
+const DISCHARGE_CURVE: [(u16, u8); 11] = [
+    (4200, 100),
+    (4100, 90),
+    (4020, 80),
+    (3950, 70),
+    (3870, 60),
+    (3840, 50),
+    (3800, 40),
+    (3770, 30),
+    (3730, 20),
+    (3690, 10),
+    (3300, 0)
+];
+
+/// How much charge is left, in broad terms
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BatteryLevel {
+    Ok,
+    /// Power use should be reduced
+    Low,
+    /// Only the bare minimum should stay on
+    Critical
+}
+
+/// Result of the last battery measurement
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BatteryStatus {
+    pub millivolts: u16,
+    pub percent: u8,
+    pub level: BatteryLevel
+}
+
+/// Convert raw VREFINT and battery pin readings to the battery voltage in millivolts, or `None` if
+/// the VREFINT reading can't be right
+pub fn to_millivolts(vrefint_cal: u16, vrefint: u16, raw: u16) -> Option<u16> {
+    if vrefint == 0 {
+        return None;
+    }
+    // Supply voltage, from how VREFINT reads compared to at 3.0 V
+    let vdda_mv = VREFINT_CAL_VDDA_MV * vrefint_cal as u32 / vrefint as u32;
+    Some((vdda_mv * raw as u32 * DIVIDER / ADC_MAX) as u16)
+}
+
+/// Estimate charge percentage by interpolating the discharge curve
+pub fn percent_from_mv(millivolts: u16) -> u8 {
+    let (top_mv, top_pct) = DISCHARGE_CURVE[0];
+    if millivolts >= top_mv {
+        return top_pct;
+    }
+
+    for pair in DISCHARGE_CURVE.windows(2) {
+        let (hi_mv, hi_pct) = pair[0];
+        let (lo_mv, lo_pct) = pair[1];
+        if millivolts >= lo_mv {
+            let frac = (millivolts - lo_mv) as u32 * (hi_pct - lo_pct) as u32 / (hi_mv - lo_mv) as u32;
+            return lo_pct + frac as u8;
+        }
+    }
+
+    0
+}
+
+/// Work out the battery level, with hysteresis when going back up
+pub fn level_from_mv(millivolts: u16, previous: BatteryLevel) -> BatteryLevel {
+    // Thresholds to go back up are higher than to go down
+    let (critical, low) = match previous {
+        BatteryLevel::Ok => (CRITICAL_MV, LOW_MV),
+        BatteryLevel::Low => (CRITICAL_MV, LOW_MV + HYSTERESIS_MV),
+        BatteryLevel::Critical => (CRITICAL_MV + HYSTERESIS_MV, LOW_MV + HYSTERESIS_MV)
+    };
+
+    if millivolts < critical {
+        BatteryLevel::Critical
+    }
+    else if millivolts < low {
+        BatteryLevel::Low
+    }
+    else {
+        BatteryLevel::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Typical VREFINT_CAL: 1.2 V at a 3.0 V supply
+    const CAL: u16 = 1638;
+
+    #[test]
+    fn millivolts() {
+        // Supply at 3.0 V, so VREFINT reads the same as when calibrated
+        assert_eq!(to_millivolts(CAL, CAL, 2730), Some(4000));
+        assert_eq!(to_millivolts(CAL, CAL, 0), Some(0));
+        // Lower supply: VREFINT reads higher, and full scale is less
+        assert_eq!(to_millivolts(CAL, 1966, 4095), Some(4998));
+        // A VREFINT reading of 0 would divide by zero
+        assert_eq!(to_millivolts(CAL, 0, 2730), None);
+    }
+
+    #[test]
+    fn percent() {
+        assert_eq!(percent_from_mv(4300), 100);
+        assert_eq!(percent_from_mv(4200), 100);
+        assert_eq!(percent_from_mv(3840), 50);
+        assert_eq!(percent_from_mv(3820), 45);
+        assert_eq!(percent_from_mv(3495), 5);
+        assert_eq!(percent_from_mv(3300), 0);
+        assert_eq!(percent_from_mv(0), 0);
+    }
+
+    #[test]
+    fn levels_going_down() {
+        assert_eq!(level_from_mv(3650, BatteryLevel::Ok), BatteryLevel::Ok);
+        assert_eq!(level_from_mv(3599, BatteryLevel::Ok), BatteryLevel::Low);
+        assert_eq!(level_from_mv(3399, BatteryLevel::Ok), BatteryLevel::Critical);
+        assert_eq!(level_from_mv(3399, BatteryLevel::Low), BatteryLevel::Critical);
+    }
+
+    #[test]
+    fn levels_going_up_need_hysteresis() {
+        assert_eq!(level_from_mv(3650, BatteryLevel::Low), BatteryLevel::Low);
+        assert_eq!(level_from_mv(3700, BatteryLevel::Low), BatteryLevel::Ok);
+        assert_eq!(level_from_mv(3450, BatteryLevel::Critical), BatteryLevel::Critical);
+        assert_eq!(level_from_mv(3500, BatteryLevel::Critical), BatteryLevel::Low);
+        assert_eq!(level_from_mv(3700, BatteryLevel::Critical), BatteryLevel::Ok);
+    }
+}
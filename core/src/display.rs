@@ -0,0 +1,854 @@
+//! Contains an implementation of [`DrawTarget`](embedded_graphics::draw_target::DrawTarget) for the Sharp Memory LCD.
+//!
+//! Most implementation details are based on [this programming guide](https://www.sharpsde.com/fileadmin/products/Displays/2016_SDE_App_Note_for_Memory_LCD_programming_V1.3.pdf).
+
+use core::fmt::{Formatter};
+use embedded_graphics::{
+    prelude::*,
+    primitives::Rectangle,
+    pixelcolor::BinaryColor
+};
+use embedded_hal::{
+    digital::v2::OutputPin,
+    spi::FullDuplex
+};
+
+const WIDTH: usize = 168;
+const HEIGHT: usize = 144;
+
+const COMMAND_WRITE_LINES: u8 = 0b10000000;
+const COMMAND_CLEAR: u8 = 0b00100000;
+const COMMAND_TOGGLE_VCOM: u8 = 0b00000000;
+
+/// How the display's VCOM signal is driven
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VcomMode {
+    /// EXTMODE = L: VCOM is toggled by the V-bit in each command, so [`SharpLcd::toggle_vcom()`] and
+    /// [`SharpLcd::flush()`] must be called at least once per second.
+    Software,
+    /// EXTMODE = H: VCOM is toggled by a signal on the EXTCOMIN pin (e.g. a GPIO toggled from a 1 Hz
+    /// interrupt), and the V-bit is ignored by the display.
+    External
+}
+
+/// Clockwise rotation of the drawn image relative to the panel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270
+}
+
+/// An implementation of [`DrawTarget`](embedded_graphics::draw_target::DrawTarget) for the Sharp Memory LCD
+///
+/// In normal mode [`BinaryColor::On`] is a black pixel, and [`BinaryColor::Off`] is the white
+/// background. Inverted mode swaps them.
+pub struct SharpLcd<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+
+    /// Contains an array for every row, and a `u8` for every 8 pixels in that row, in panel
+    /// orientation. A set bit is a white pixel, as that's how the LCD expects it.
+    /// Byte order: leftmost pixels are in the MSB (the LCD expects the leftmost pixel first, and the SPI is MSB first)
+    framebuffer: [[u8; WIDTH/8]; HEIGHT],
+    /// Copy of what was last sent to the panel, in the same format as `framebuffer`. Used to skip
+    /// sending lines that were drawn to but didn't actually change.
+    /// Assumes the panel is white on startup, so [`SharpLcd::send_clear()`] should be called first.
+    displayed: [[u8; WIDTH/8]; HEIGHT],
+    /// Keeps track of which lines have been updated. LSB means lower y-value
+    updated_lines: [u8; HEIGHT/8],
+    vcom: bool,
+    vcom_mode: VcomMode,
+    rotation: Rotation,
+    inverted: bool
+}
+impl<SPI, CS> core::fmt::Debug for SharpLcd<SPI, CS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SharpLcd")
+            .field("framebuffer", &self.framebuffer)
+            .field("displayed", &self.displayed)
+            .field("updated_lines", &self.updated_lines)
+            .field("vcom", &self.vcom)
+            .field("vcom_mode", &self.vcom_mode)
+            .field("rotation", &self.rotation)
+            .field("inverted", &self.inverted)
+            .finish_non_exhaustive() // because the SPI and CS don't have Debug
+    }
+}
+
+impl<SPI: FullDuplex<u8>, CS: OutputPin> SharpLcd<SPI, CS> {
+    /// Create a new display. `vcom_mode` must match how the display's EXTMODE pin is wired.
+    pub fn new(spi: SPI, mut cs: CS, vcom_mode: VcomMode) -> Self {
+        let _ = cs.set_low();
+
+        Self {
+            spi,
+            cs,
+            framebuffer: [[0xFF; WIDTH/8]; HEIGHT],
+            displayed: [[0xFF; WIDTH/8]; HEIGHT],
+            updated_lines: [0; HEIGHT/8],
+            vcom: false,
+            vcom_mode,
+            rotation: Rotation::Deg0,
+            inverted: false
+        }
+    }
+
+    /// Clear the screen fully white by sending a command to the screen.
+    /// If inverted, white is [`BinaryColor::On`].
+    pub fn send_clear(&mut self) -> Result<(), SPI::Error> {
+        self.framebuffer = [[0xFF; WIDTH/8]; HEIGHT];
+        self.displayed = [[0xFF; WIDTH/8]; HEIGHT];
+
+        let command = self.format_command(COMMAND_CLEAR);
+        self.transaction(|disp| {
+            // Header
+            disp.write_byte(command)?;
+            // Trailer
+            disp.write_byte(0x00)
+        })?;
+
+        // Lines have been flushed
+        self.updated_lines = [0x00; HEIGHT/8];
+
+        Ok(())
+    }
+
+    /// Flush changes to the screen. Only lines that differ from what's on the screen are sent.
+    pub fn flush(&mut self) -> Result<(), SPI::Error> {
+        //TODO: Might want to optimize with DMA, that'd require more params
+
+        // Don't send lines that haven't actually changed
+        for n in 0..HEIGHT {
+            if self.framebuffer[n] == self.displayed[n] {
+                self.updated_lines[n / 8] &= !(1u8 << (n % 8));
+            }
+        }
+
+        // If no lines have been updated and VCOM is driven externally, there's nothing to send
+        if self.updated_lines == [0x00; HEIGHT/8] && self.vcom_mode == VcomMode::External {
+            return Ok(());
+        }
+
+        // If no lines have been updated since last flush, then just write Toggle VCOM message
+        if self.updated_lines == [0x00; HEIGHT/8] {
+            let command = self.format_command(COMMAND_TOGGLE_VCOM);
+            self.transaction(|disp| {
+                // Header
+                disp.write_byte(command)?;
+                // Trailer
+                disp.write_byte(0x00)
+            })?;
+        }
+
+        // If framebuffer has been updated, only send changed lines
+        else {
+            let command = self.format_command(COMMAND_WRITE_LINES);
+            self.transaction(|disp| {
+                // Header
+                disp.write_byte(command)?;
+
+                // Line data
+                for n in 0..HEIGHT {
+                    // Only send line if changed
+                    if disp.updated_lines[n / 8] & (1u8 << (n % 8)) != 0 {
+                        // Line number: 1-based, and sent LSB first even though the SPI is MSB first
+                        disp.write_byte((n as u8 + 1).reverse_bits())?;
+                        // Line data
+                        for i in 0..WIDTH/8 {
+                            disp.write_byte(disp.framebuffer[n][i])?;
+                        }
+                        // Line trailer
+                        disp.write_byte(0x00)?;
+                    }
+                }
+
+                // Trailer
+                disp.write_byte(0x00)
+            })?;
+
+            // Remember what was sent, and clear changed lines
+            for n in 0..HEIGHT {
+                if self.updated_lines[n / 8] & (1u8 << (n % 8)) != 0 {
+                    self.displayed[n] = self.framebuffer[n];
+                }
+            }
+            self.updated_lines = [0x00; HEIGHT / 8];
+        }
+
+        Ok(())
+    }
+
+    /// Run `f` with CS asserted, making sure CS is un-asserted afterwards even if `f` errors
+    fn transaction<F>(&mut self, f: F) -> Result<(), SPI::Error>
+    where F: FnOnce(&mut Self) -> Result<(), SPI::Error> {
+        // Assert CS
+        let _ = self.cs.set_high(); // cannot error
+        let res = f(self);
+        // Un-assert CS
+        let _ = self.cs.set_low();
+
+        res
+    }
+
+    /// Send a single byte, blocking until it has been sent.
+    /// The received byte is read back and discarded, otherwise the SPI's RX register overruns.
+    fn write_byte(&mut self, byte: u8) -> Result<(), SPI::Error> {
+        nb::block!(self.spi.send(byte))?;
+        nb::block!(self.spi.read())?;
+
+        Ok(())
+    }
+}
+
+impl<SPI, CS> SharpLcd<SPI, CS> {
+    /// Clear the screen to the background color ([`BinaryColor::Off`]). Needs to be flushed afterward.
+    pub fn clear(&mut self) {
+        // Cannot error
+        let _ = DrawTarget::clear(self, BinaryColor::Off);
+    }
+
+    /// Set the rotation of everything drawn afterwards. Already-drawn pixels aren't moved, so the
+    /// screen should be redrawn afterwards.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Set whether colors are inverted (white-on-black). The framebuffer is inverted in place, so
+    /// already-drawn pixels stay consistent. Needs to be flushed afterward.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        if inverted == self.inverted {
+            return;
+        }
+        self.inverted = inverted;
+
+        for line in self.framebuffer.iter_mut() {
+            for byte in line.iter_mut() {
+                *byte = !*byte;
+            }
+        }
+        // All lines have been updated
+        self.updated_lines = [0xFF; HEIGHT/8];
+    }
+
+    /// Get a [`DrawTarget`] for a sub-window of the screen. Drawing is done in coordinates relative
+    /// to the top-left of `area`, and clipped to it, so only the lines in `area` are marked updated.
+    pub fn window(&mut self, area: &Rectangle) -> Window<'_, SPI, CS> {
+        let area = area.intersection(&self.bounding_box());
+        Window {
+            display: self,
+            area
+        }
+    }
+
+    /// Toggle the VCOM value. This should be done at least once per second to prevent burn-in.
+    /// [`SharpLcd::flush()`] should be called afterwards.
+    /// Does nothing if VCOM is driven externally.
+    pub fn toggle_vcom(&mut self) {
+        if self.vcom_mode == VcomMode::Software {
+            self.vcom = !self.vcom;
+        }
+    }
+
+    /// Write a single pixel.
+    #[inline(always)]
+    fn write_pixel(&mut self, pixel: Pixel<BinaryColor>) {
+        // Ignore it if it's outside of the screen bounds
+        if !self.bounding_box().contains(pixel.0) {
+            return;
+        }
+
+        // Convert to panel coordinates. Can't underflow because bounds have been checked
+        let (x, y) = self.to_panel(pixel.0);
+
+        // Write to framebuffer. Shouldn't panic because bounds have been checked
+        if self.is_white(pixel.1) {
+            // Or'd with bit to set it
+            self.framebuffer[y][x / 8] |= 0x80u8 >> (x % 8);
+        }
+        else {
+            // And'd with inverse of byte to unset it
+            self.framebuffer[y][x / 8] &= !(0x80u8 >> (x % 8));
+        }
+
+        // Mark line as updated
+        self.updated_lines[y / 8] |= 1u8 << (y % 8);
+    }
+
+    /// Fill the pixels `x0..x1` of panel line `y` with white if `white` is set, otherwise black,
+    /// a whole byte at a time. The line isn't marked as updated.
+    fn fill_panel_line(&mut self, y: usize, x0: usize, x1: usize, white: bool) {
+        let fill = if white { 0xFF } else { 0x00 };
+        for i in (x0 / 8)..x1.div_ceil(8) {
+            // Range of bits in this byte to fill, counting from the MSB
+            let start = x0.saturating_sub(i * 8);
+            let end = (x1 - i * 8).min(8);
+            // `end - start` ones, shifted right by `start`
+            let mask = ((0xFF00u16 >> (end - start)) as u8) >> start;
+
+            let byte = &mut self.framebuffer[y][i];
+            *byte = (*byte & !mask) | (fill & mask);
+        }
+    }
+
+    /// Mark panel lines `y0..y1` as updated
+    fn mark_lines(&mut self, y0: usize, y1: usize) {
+        for y in y0..y1 {
+            self.updated_lines[y / 8] |= 1u8 << (y % 8);
+        }
+    }
+
+    /// Whether a color is a white pixel, i.e. a set bit. Normally, On is black.
+    #[inline(always)]
+    fn is_white(&self, color: BinaryColor) -> bool {
+        color.is_on() == self.inverted
+    }
+
+    /// Convert a point in rotated coordinates to panel coordinates. The point must be in bounds.
+    #[inline(always)]
+    fn to_panel(&self, point: Point) -> (usize, usize) {
+        let (x, y) = (point.x as usize, point.y as usize);
+        match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (WIDTH - 1 - y, x),
+            Rotation::Deg180 => (WIDTH - 1 - x, HEIGHT - 1 - y),
+            Rotation::Deg270 => (y, HEIGHT - 1 - x)
+        }
+    }
+
+    /// Format a display command with the V-bit set to the VCOM state.
+    /// The V-bit is left clear if VCOM is driven externally.
+    #[inline(always)]
+    fn format_command(&self, command: u8) -> u8 {
+        match self.vcom_mode {
+            VcomMode::Software => command | ((self.vcom as u8) << 6),
+            VcomMode::External => command
+        }
+    }
+}
+
+impl<SPI, CS> Dimensions for SharpLcd<SPI, CS> {
+    fn bounding_box(&self) -> Rectangle {
+        match self.rotation {
+            Rotation::Deg0 | Rotation::Deg180 =>
+                Rectangle::new(Point::new(0, 0), Size::new(WIDTH as u32, HEIGHT as u32)),
+            Rotation::Deg90 | Rotation::Deg270 =>
+                Rectangle::new(Point::new(0, 0), Size::new(HEIGHT as u32, WIDTH as u32))
+        }
+    }
+}
+
+impl<SPI, CS> DrawTarget for SharpLcd<SPI, CS> {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error> where I: IntoIterator<Item=Pixel<Self::Color>> {
+        // Draw each pixel
+        for pixel in pixels {
+            self.write_pixel(pixel);
+        }
+
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error> where I: IntoIterator<Item=Self::Color> {
+        // Only the unrotated case lines up with framebuffer bytes, so fall back to per-pixel otherwise
+        if self.rotation != Rotation::Deg0 {
+            return self.draw_iter(area.points().zip(colors).map(|(p, c)| Pixel(p, c)));
+        }
+
+        let clipped = area.intersection(&self.bounding_box());
+        if clipped.is_zero_sized() {
+            return Ok(());
+        }
+        let (x0, x1) = (clipped.top_left.x as usize, (clipped.top_left.x + clipped.size.width as i32) as usize);
+        // Number of colors before and after the visible part of each row
+        let skip_left = (clipped.top_left.x - area.top_left.x) as usize;
+        let skip_right = area.size.width as usize - clipped.size.width as usize - skip_left;
+
+        let mut colors = colors.into_iter();
+        for y in area.rows() {
+            // Skip rows outside of the screen
+            if !clipped.rows().contains(&y) {
+                if area.size.width > 0 && colors.nth(area.size.width as usize - 1).is_none() {
+                    return Ok(());
+                }
+                continue;
+            }
+            let y = y as usize;
+
+            if skip_left > 0 && colors.nth(skip_left - 1).is_none() {
+                return Ok(());
+            }
+
+            self.mark_lines(y, y + 1);
+
+            // Build up a byte of pixels, and which bits of it are drawn
+            let mut bits = 0u8;
+            let mut mask = 0u8;
+            for x in x0..x1 {
+                let color = colors.next();
+                if let Some(color) = color {
+                    let bit = 0x80u8 >> (x % 8);
+                    mask |= bit;
+                    if self.is_white(color) {
+                        bits |= bit;
+                    }
+                }
+
+                // Write the byte when it's complete, or when the colors run out
+                if x % 8 == 7 || x == x1 - 1 || color.is_none() {
+                    let byte = &mut self.framebuffer[y][x / 8];
+                    *byte = (*byte & !mask) | bits;
+                    bits = 0;
+                    mask = 0;
+                }
+                if color.is_none() {
+                    return Ok(());
+                }
+            }
+
+            if skip_right > 0 && colors.nth(skip_right - 1).is_none() {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        // Clip to screen, and convert corners to panel coordinates
+        let area = area.intersection(&self.bounding_box());
+        let (a, b) = match area.bottom_right() {
+            Some(br) => (self.to_panel(area.top_left), self.to_panel(br)),
+            None => return Ok(()) // nothing to draw
+        };
+        let (x0, x1) = (a.0.min(b.0), a.0.max(b.0) + 1);
+        let (y0, y1) = (a.1.min(b.1), a.1.max(b.1) + 1);
+
+        let white = self.is_white(color);
+        for y in y0..y1 {
+            self.fill_panel_line(y, x0, x1, white);
+        }
+        self.mark_lines(y0, y1);
+
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let fill = if self.is_white(color) { 0xFF } else { 0x00 };
+        self.framebuffer = [[fill; WIDTH/8]; HEIGHT];
+        // All lines have been updated
+        self.updated_lines = [0xFF; HEIGHT/8];
+
+        Ok(())
+    }
+}
+
+/// A clipped sub-window of a [`SharpLcd`], with its own coordinate system. Created with [`SharpLcd::window()`].
+pub struct Window<'a, SPI, CS> {
+    display: &'a mut SharpLcd<SPI, CS>,
+    /// Area of the window, in display coordinates
+    area: Rectangle
+}
+impl<SPI, CS> core::fmt::Debug for Window<'_, SPI, CS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Window")
+            .field("area", &self.area)
+            .finish_non_exhaustive() // display is omitted because its framebuffer is huge
+    }
+}
+
+impl<SPI, CS> Dimensions for Window<'_, SPI, CS> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(Point::zero(), self.area.size)
+    }
+}
+
+impl<SPI, CS> DrawTarget for Window<'_, SPI, CS> {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error> where I: IntoIterator<Item=Pixel<Self::Color>> {
+        // Translate each pixel to display coordinates and draw it, if it's inside the window
+        for Pixel(point, color) in pixels {
+            let point = point + self.area.top_left;
+            if self.area.contains(point) {
+                self.display.write_pixel(Pixel(point, color));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error> where I: IntoIterator<Item=Self::Color> {
+        let translated = area.translate(self.area.top_left);
+        // Pass it on to the display if it doesn't need clipping, otherwise clip per-pixel
+        if self.area.intersection(&translated) == translated {
+            self.display.fill_contiguous(&translated, colors)
+        }
+        else {
+            self.draw_iter(area.points().zip(colors).map(|(p, c)| Pixel(p, c)))
+        }
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        // Translate and clip the area, and pass it on to the display
+        let area = area.translate(self.area.top_left).intersection(&self.area);
+        self.display.fill_solid(&area, color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Host-side tests that check the exact byte stream against the Sharp programming guide.
+
+    use super::*;
+    use std::{
+        cell::RefCell,
+        rc::Rc,
+        vec::Vec
+    };
+    use embedded_graphics::primitives::{Line, PrimitiveStyle};
+
+    /// Something that happened on the bus
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    enum Event {
+        /// CS was set to this level
+        Cs(bool),
+        /// A byte was clocked out
+        Byte(u8)
+    }
+
+    type Log = Rc<RefCell<Vec<Event>>>;
+
+    #[derive(Debug)]
+    enum MockError {
+        /// A byte was sent before the previous received byte was read
+        Overrun
+    }
+
+    /// Mock SPI that records sent bytes, and requires every sent byte to be read back.
+    /// Every other call returns `WouldBlock` to make sure the driver retries properly.
+    struct MockSpi {
+        log: Log,
+        unread: bool,
+        busy: bool
+    }
+
+    impl FullDuplex<u8> for MockSpi {
+        type Error = MockError;
+
+        fn read(&mut self) -> nb::Result<u8, Self::Error> {
+            self.busy = !self.busy;
+            if self.busy || !self.unread {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.unread = false;
+            Ok(0xFF)
+        }
+
+        fn send(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+            self.busy = !self.busy;
+            if self.busy {
+                return Err(nb::Error::WouldBlock);
+            }
+            if self.unread {
+                return Err(nb::Error::Other(MockError::Overrun));
+            }
+            self.unread = true;
+            self.log.borrow_mut().push(Event::Byte(byte));
+            Ok(())
+        }
+    }
+
+    /// Mock CS pin that records level changes
+    struct MockPin {
+        log: Log
+    }
+
+    impl OutputPin for MockPin {
+        type Error = core::convert::Infallible;
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.log.borrow_mut().push(Event::Cs(false));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.log.borrow_mut().push(Event::Cs(true));
+            Ok(())
+        }
+    }
+
+    /// Create a display with mock peripherals, with the log cleared after construction
+    fn mock_display(vcom_mode: VcomMode) -> (SharpLcd<MockSpi, MockPin>, Log) {
+        let log = Log::default();
+        let disp = SharpLcd::new(
+            MockSpi { log: log.clone(), unread: false, busy: false },
+            MockPin { log: log.clone() },
+            vcom_mode
+        );
+        log.borrow_mut().clear();
+        (disp, log)
+    }
+
+    /// Wrap the given bytes in a CS high/low pair
+    fn transaction(bytes: &[u8]) -> Vec<Event> {
+        let mut events = vec![Event::Cs(true)];
+        events.extend(bytes.iter().map(|&b| Event::Byte(b)));
+        events.push(Event::Cs(false));
+        events
+    }
+
+    #[test]
+    fn clear() {
+        let (mut disp, log) = mock_display(VcomMode::Software);
+        disp.send_clear().unwrap();
+        assert_eq!(*log.borrow(), transaction(&[0b0010_0000, 0x00]));
+    }
+
+    #[test]
+    fn vcom_toggle() {
+        let (mut disp, log) = mock_display(VcomMode::Software);
+        disp.flush().unwrap();
+        disp.toggle_vcom();
+        disp.flush().unwrap();
+
+        let mut expected = transaction(&[0b0000_0000, 0x00]);
+        expected.extend(transaction(&[0b0100_0000, 0x00]));
+        assert_eq!(*log.borrow(), expected);
+    }
+
+    #[test]
+    fn partial_update() {
+        let (mut disp, log) = mock_display(VcomMode::Software);
+        // Top-left pixel on line 0, and the rightmost pixel of line 9
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        Pixel(Point::new(WIDTH as i32 - 1, 9), BinaryColor::On).draw(&mut disp).unwrap();
+        disp.flush().unwrap();
+
+        let mut line_1 = [0xFFu8; WIDTH/8];
+        line_1[0] = 0b0111_1111;
+        let mut line_10 = [0xFFu8; WIDTH/8];
+        line_10[WIDTH/8 - 1] = 0b1111_1110;
+
+        let mut bytes = vec![0b1000_0000];
+        bytes.push(0b1000_0000); // line 1, LSB first
+        bytes.extend_from_slice(&line_1);
+        bytes.push(0x00);
+        bytes.push(0b0101_0000); // line 10, LSB first
+        bytes.extend_from_slice(&line_10);
+        bytes.push(0x00);
+        bytes.push(0x00);
+        assert_eq!(*log.borrow(), transaction(&bytes));
+
+        // Flushed lines aren't sent again
+        log.borrow_mut().clear();
+        disp.flush().unwrap();
+        assert_eq!(*log.borrow(), transaction(&[0b0000_0000, 0x00]));
+    }
+
+    #[test]
+    fn last_line_address() {
+        let (mut disp, log) = mock_display(VcomMode::Software);
+        Line::new(Point::new(0, HEIGHT as i32 - 1), Point::new(WIDTH as i32 - 1, HEIGHT as i32 - 1))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut disp)
+            .unwrap();
+        disp.flush().unwrap();
+
+        let mut bytes = vec![0b1000_0000, (HEIGHT as u8).reverse_bits()];
+        bytes.extend_from_slice(&[0x00; WIDTH/8]);
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        assert_eq!(*log.borrow(), transaction(&bytes));
+    }
+
+    #[test]
+    fn external_vcom() {
+        let (mut disp, log) = mock_display(VcomMode::External);
+        disp.toggle_vcom();
+        // Nothing to send if nothing changed
+        disp.flush().unwrap();
+        assert!(log.borrow().is_empty());
+
+        // V-bit is never set
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        disp.flush().unwrap();
+        assert_eq!(log.borrow()[1], Event::Byte(0b1000_0000));
+        disp.send_clear().unwrap();
+        assert_eq!(log.borrow()[log.borrow().len() - 3], Event::Byte(0b0010_0000));
+    }
+
+    #[test]
+    fn rotation() {
+        let (mut disp, _log) = mock_display(VcomMode::Software);
+        disp.set_rotation(Rotation::Deg90);
+        assert_eq!(disp.bounding_box().size, Size::new(HEIGHT as u32, WIDTH as u32));
+
+        // Top-left of the image is at the top-right of the panel
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        assert_eq!(disp.framebuffer[0][WIDTH/8 - 1], 0b1111_1110);
+        // Out of bounds in rotated coordinates
+        Pixel(Point::new(HEIGHT as i32, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        assert_eq!(disp.updated_lines[HEIGHT/8 - 1], 0);
+
+        disp.set_rotation(Rotation::Deg180);
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        assert_eq!(disp.framebuffer[HEIGHT - 1][WIDTH/8 - 1], 0b1111_1110);
+
+        disp.set_rotation(Rotation::Deg270);
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        assert_eq!(disp.framebuffer[HEIGHT - 1][0], 0b0111_1111);
+    }
+
+    #[test]
+    fn inversion() {
+        let (mut disp, _log) = mock_display(VcomMode::Software);
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        disp.flush().unwrap();
+
+        // Existing pixels are inverted
+        disp.set_inverted(true);
+        assert_eq!(disp.framebuffer[0][0], 0b1000_0000);
+        assert_eq!(disp.framebuffer[1][0], 0x00);
+        assert_eq!(disp.updated_lines, [0xFF; HEIGHT/8]);
+
+        // On is now white
+        Pixel(Point::new(1, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        assert_eq!(disp.framebuffer[0][0], 0b1100_0000);
+        disp.clear();
+        assert_eq!(disp.framebuffer[0][0], 0x00);
+    }
+
+    #[test]
+    fn window() {
+        let (mut disp, _log) = mock_display(VcomMode::Software);
+        let area = Rectangle::new(Point::new(8, 20), Size::new(16, 4));
+        let mut window = disp.window(&area);
+        // Local coordinates
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut window).unwrap();
+        // Clipped
+        Pixel(Point::new(0, 4), BinaryColor::On).draw(&mut window).unwrap();
+        Pixel(Point::new(16, 0), BinaryColor::On).draw(&mut window).unwrap();
+
+        assert_eq!(disp.framebuffer[20][1], 0b0111_1111);
+        assert_eq!(disp.framebuffer[20][2], 0xFF);
+        let mut updated_lines = [0u8; HEIGHT/8];
+        updated_lines[2] = 0b0001_0000;
+        assert_eq!(disp.updated_lines, updated_lines);
+    }
+
+    /// Areas that cover byte boundaries, partial bytes, and the screen edges
+    fn test_areas() -> Vec<Rectangle> {
+        vec![
+            Rectangle::new(Point::new(0, 0), Size::new(WIDTH as u32, HEIGHT as u32)),
+            Rectangle::new(Point::new(3, 5), Size::new(2, 7)),
+            Rectangle::new(Point::new(5, 1), Size::new(30, 3)),
+            Rectangle::new(Point::new(8, 8), Size::new(16, 16)),
+            Rectangle::new(Point::new(-5, -3), Size::new(20, 10)),
+            Rectangle::new(Point::new(150, 130), Size::new(40, 40)),
+            Rectangle::new(Point::new(10, 10), Size::new(0, 5))
+        ]
+    }
+
+    /// Check that the fast paths give the same result as drawing pixel by pixel
+    #[test]
+    fn fill_matches_per_pixel() {
+        for &rotation in &[Rotation::Deg0, Rotation::Deg90, Rotation::Deg180, Rotation::Deg270] {
+            for &inverted in &[false, true] {
+                for area in test_areas() {
+                    let (mut fast, _) = mock_display(VcomMode::Software);
+                    let (mut slow, _) = mock_display(VcomMode::Software);
+                    for disp in [&mut fast, &mut slow] {
+                        disp.set_rotation(rotation);
+                        disp.set_inverted(inverted);
+                        disp.updated_lines = [0; HEIGHT/8];
+                    }
+
+                    // Arbitrary pattern
+                    let colors = (0u32..).map(|i| BinaryColor::from((i * 7 + i / 5) % 3 == 0));
+                    fast.fill_contiguous(&area, colors.clone()).unwrap();
+                    slow.draw_iter(area.points().zip(colors).map(|(p, c)| Pixel(p, c))).unwrap();
+                    assert_eq!(fast.framebuffer, slow.framebuffer, "fill_contiguous {:?} {:?} {}", area, rotation, inverted);
+                    assert_eq!(fast.updated_lines, slow.updated_lines, "fill_contiguous {:?} {:?} {}", area, rotation, inverted);
+
+                    // Short iterator
+                    fast.fill_contiguous(&area, core::iter::repeat_n(BinaryColor::On, 11)).unwrap();
+                    slow.draw_iter(area.points().take(11).map(|p| Pixel(p, BinaryColor::On))).unwrap();
+                    assert_eq!(fast.framebuffer, slow.framebuffer, "short fill_contiguous {:?} {:?} {}", area, rotation, inverted);
+
+                    fast.fill_solid(&area, BinaryColor::On).unwrap();
+                    slow.draw_iter(area.points().map(|p| Pixel(p, BinaryColor::On))).unwrap();
+                    assert_eq!(fast.framebuffer, slow.framebuffer, "fill_solid {:?} {:?} {}", area, rotation, inverted);
+                    assert_eq!(fast.updated_lines, slow.updated_lines, "fill_solid {:?} {:?} {}", area, rotation, inverted);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clear_color() {
+        let (mut disp, _log) = mock_display(VcomMode::Software);
+        DrawTarget::clear(&mut disp, BinaryColor::On).unwrap();
+        assert_eq!(disp.framebuffer, [[0x00; WIDTH/8]; HEIGHT]);
+        assert_eq!(disp.updated_lines, [0xFF; HEIGHT/8]);
+        disp.clear();
+        assert_eq!(disp.framebuffer, [[0xFF; WIDTH/8]; HEIGHT]);
+    }
+
+    /// Compare the fast paths against drawing pixel by pixel.
+    /// Run with `cargo test --release -- --ignored --nocapture bench_fill`.
+    #[test]
+    #[ignore]
+    fn bench_fill() {
+        use std::time::Instant;
+
+        const ITERATIONS: u32 = 1000;
+        let (mut disp, _log) = mock_display(VcomMode::Software);
+        let area = disp.bounding_box();
+        let colors = (0u32..).map(|i| BinaryColor::from(i % 3 == 0));
+
+        let mut bench = |name: &str, f: &mut dyn FnMut(&mut SharpLcd<MockSpi, MockPin>)| {
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                f(&mut disp);
+                std::hint::black_box(&disp.framebuffer);
+            }
+            std::println!("{:>24}: {:?}/iter", name, start.elapsed() / ITERATIONS);
+        };
+
+        bench("per-pixel solid", &mut |d| d.draw_iter(area.points().map(|p| Pixel(p, BinaryColor::On))).unwrap());
+        bench("fill_solid", &mut |d| d.fill_solid(&area, BinaryColor::On).unwrap());
+        bench("clear", &mut |d| DrawTarget::clear(d, BinaryColor::On).unwrap());
+        bench("per-pixel contiguous", &mut |d| d.draw_iter(area.points().zip(colors.clone()).map(|(p, c)| Pixel(p, c))).unwrap());
+        bench("fill_contiguous", &mut |d| d.fill_contiguous(&area, colors.clone()).unwrap());
+    }
+
+    #[test]
+    fn unchanged_lines_skipped() {
+        let (mut disp, log) = mock_display(VcomMode::Software);
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        Pixel(Point::new(0, 1), BinaryColor::On).draw(&mut disp).unwrap();
+        disp.flush().unwrap();
+
+        // Redrawing the same frame only toggles VCOM
+        log.borrow_mut().clear();
+        disp.clear();
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        Pixel(Point::new(0, 1), BinaryColor::On).draw(&mut disp).unwrap();
+        disp.flush().unwrap();
+        assert_eq!(*log.borrow(), transaction(&[0b0000_0000, 0x00]));
+
+        // Only the line that changed is sent
+        log.borrow_mut().clear();
+        disp.clear();
+        Pixel(Point::new(0, 0), BinaryColor::On).draw(&mut disp).unwrap();
+        disp.flush().unwrap();
+        let mut bytes = vec![0b1000_0000, 0b0100_0000]; // line 2, LSB first
+        bytes.extend_from_slice(&[0xFF; WIDTH/8]);
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        assert_eq!(*log.borrow(), transaction(&bytes));
+    }
+}
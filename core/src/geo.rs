@@ -0,0 +1,182 @@
+//! Positions, and the distance and bearing between them.
+//!
+//! Uses the equirectangular approximation, which only needs integer maths, and is accurate to
+//! well under 1% over the distances a watch is used to navigate (up to 100 km or so).
+
+use core::fmt::Write;
+
+use arrayvec::ArrayString;
+
+use crate::nmea::Coord;
+use crate::settings::Units;
+use crate::trig;
+
+/// Length of a degree of latitude, using the mean radius of the Earth
+const METERS_PER_DEGREE: i64 = 111_195;
+/// Units of [`Position`] per degree
+const MICRO: i64 = 1_000_000;
+/// Feet per thousand meters
+const FEET_PER_KM: u64 = 3281;
+/// Meters per thousand miles
+const METERS_PER_KMILE: u64 = 1_609_344;
+
+/// A position, in millionths of a degree (about 0.1 m). North and east are positive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub latitude: i32,
+    pub longitude: i32
+}
+
+impl Position {
+    pub fn from_coords(latitude: &Coord, longitude: &Coord) -> Self {
+        Self {
+            latitude: latitude.micro_degrees(),
+            longitude: longitude.micro_degrees()
+        }
+    }
+
+    /// Offset to `to` in meters, as (east, north)
+    pub fn offset_m(&self, to: &Position) -> (i32, i32) {
+        let north = (to.latitude as i64 - self.latitude as i64) * METERS_PER_DEGREE / MICRO;
+
+        // Take the short way round across the antimeridian
+        let mut dlon = to.longitude as i64 - self.longitude as i64;
+        if dlon > 180 * MICRO {
+            dlon -= 360 * MICRO;
+        }
+        else if dlon < -180 * MICRO {
+            dlon += 360 * MICRO;
+        }
+        // Degrees of longitude shrink with the cosine of the latitude
+        let mean_latitude = (self.latitude as i64 + to.latitude as i64).unsigned_abs() / 2;
+        let east = dlon * METERS_PER_DEGREE * cos_micro(mean_latitude) / trig::ONE as i64 / MICRO;
+
+        (clamp(east), clamp(north))
+    }
+
+    /// Distance to `to` in meters
+    pub fn distance_m(&self, to: &Position) -> u32 {
+        let (east, north) = self.offset_m(to);
+        let squared = (east as i64 * east as i64 + north as i64 * north as i64) as u64;
+        trig::isqrt(squared) as u32
+    }
+
+    /// Bearing to `to`, in degrees clockwise from true north
+    pub fn bearing_deg(&self, to: &Position) -> u32 {
+        let (east, north) = self.offset_m(to);
+        trig::atan2(east, north)
+    }
+}
+
+/// Format a distance in meters for display, e.g. "850 m", "1.25 km" or "0.4 mi". Switches to
+/// the larger unit once the smaller one would need four digits, and shows fewer decimals the
+/// larger the distance gets.
+pub fn format_distance(meters: u32, units: Units) -> ArrayString<12> {
+    let (small, small_unit, large_milli, large_unit) = match units {
+        Units::Metric => (meters as u64, "m", meters as u64, "km"),
+        Units::Imperial => (
+            meters as u64 * FEET_PER_KM / 1000,
+            "ft",
+            meters as u64 * 1_000_000 / METERS_PER_KMILE,
+            "mi"
+        )
+    };
+
+    // At most 10 digits and the unit, so this can't fail
+    let mut text = ArrayString::new();
+    // Round before picking the precision, so e.g. 9.996 km shows as "10.0 km" not "10.00 km"
+    let hundredths = (large_milli + 5) / 10;
+    let tenths = (large_milli + 50) / 100;
+    let _ = if small < 1000 {
+        write!(text, "{} {}", small, small_unit)
+    }
+    else if hundredths < 1000 {
+        write!(text, "{}.{:02} {}", hundredths / 100, hundredths % 100, large_unit)
+    }
+    else if tenths < 1000 {
+        write!(text, "{}.{} {}", tenths / 10, tenths % 10, large_unit)
+    }
+    else {
+        write!(text, "{} {}", (large_milli + 500) / 1000, large_unit)
+    };
+    text
+}
+
+/// Cosine of an angle in millionths of a degree, scaled by [`trig::ONE`], interpolating between
+/// whole degrees
+fn cos_micro(angle: u64) -> i64 {
+    let degrees = (angle / MICRO as u64) as u32;
+    let frac = (angle % MICRO as u64) as i64;
+    let (a, b) = (trig::cos(degrees) as i64, trig::cos(degrees + 1) as i64);
+    a + (b - a) * frac / MICRO
+}
+
+fn clamp(n: i64) -> i32 {
+    n.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(latitude: f64, longitude: f64) -> Position {
+        Position {
+            latitude: (latitude * 1e6) as i32,
+            longitude: (longitude * 1e6) as i32
+        }
+    }
+
+    #[test]
+    fn north_south() {
+        let (a, b) = (pos(51.5, -0.1), pos(51.6, -0.1));
+        assert_eq!(a.offset_m(&b), (0, 11_119));
+        assert_eq!(a.distance_m(&b), 11_119);
+        assert_eq!(a.bearing_deg(&b), 0);
+        assert_eq!(b.bearing_deg(&a), 180);
+    }
+
+    #[test]
+    fn east_west_shrinks_with_latitude() {
+        let (a, b) = (pos(60.0, 10.0), pos(60.0, 11.0));
+        // cos(60°) = 0.5
+        let (east, north) = a.offset_m(&b);
+        assert!((east - 55_597).abs() < 100, "{}", east);
+        assert_eq!(north, 0);
+        assert_eq!(a.bearing_deg(&b), 90);
+        assert_eq!(b.bearing_deg(&a), 270);
+    }
+
+    #[test]
+    fn diagonal() {
+        // About 1 km north east, on the equator
+        let (a, b) = (pos(0.0, 0.0), pos(0.00636, 0.00636));
+        assert_eq!(a.bearing_deg(&b), 45);
+        assert!((a.distance_m(&b) as i32 - 1000).abs() < 2);
+    }
+
+    #[test]
+    fn distances() {
+        let metric = |m| format_distance(m, Units::Metric);
+        assert_eq!(&metric(0), "0 m");
+        assert_eq!(&metric(999), "999 m");
+        assert_eq!(&metric(1000), "1.00 km");
+        assert_eq!(&metric(9_994), "9.99 km");
+        assert_eq!(&metric(9_996), "10.0 km");
+        assert_eq!(&metric(12_345), "12.3 km");
+        assert_eq!(&metric(123_456), "123 km");
+        assert_eq!(&metric(u32::MAX), "4294967 km");
+
+        let imperial = |m| format_distance(m, Units::Imperial);
+        assert_eq!(&imperial(100), "328 ft");
+        assert_eq!(&imperial(400), "0.25 mi");
+        assert_eq!(&imperial(16_093), "10.0 mi");
+        assert_eq!(&imperial(u32::MAX), "2668769 mi");
+    }
+
+    #[test]
+    fn antimeridian() {
+        let (a, b) = (pos(0.0, 179.999), pos(0.0, -179.999));
+        assert_eq!(a.bearing_deg(&b), 90);
+        assert!(a.distance_m(&b) < 300);
+    }
+}
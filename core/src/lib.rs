@@ -0,0 +1,19 @@
+//! The parts of the watch code that don't touch the MCU: the display driver (generic over the
+//! SPI bus), NMEA parsing, fixed-point maths, battery levels, settings and record layouts, and the
+//! widget toolkit.
+//!
+//! Kept in their own crate, without the HAL, so their tests can run on the host:
+//! `cargo test -p gps-watch-core --target <host triple>`
+
+#![cfg_attr(not(test), no_std)] // std is only used for host-side unit tests
+
+pub mod display;
+pub mod nmea;
+pub mod trig;
+pub mod geo;
+pub mod track;
+pub mod activity;
+pub mod settings;
+pub mod waypoints;
+pub mod battery;
+pub mod widgets;
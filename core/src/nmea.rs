@@ -0,0 +1,599 @@
+//! NMEA 0183 parser using fixed-point arithmetic.
+//!
+//! Bytes are fed in one at a time as they're received, and a sentence is returned once a whole
+//! one has been received and its checksum checked. Sentence types that aren't needed are skipped.
+//! Numbers with decimals are kept in fixed point (the unit is in the field's name or docs), since
+//! there's no FPU.
+
+use arrayvec::ArrayVec;
+use chrono::{NaiveDate, NaiveTime};
+
+/// Most satellites listed in a GSA sentence
+pub const GSA_SATELLITES: usize = 12;
+/// Most satellites listed in a GSV sentence
+pub const GSV_SATELLITES: usize = 4;
+
+/// NMEA 0183 Parser
+#[derive(Debug, Default)]
+pub struct NmeaParser {
+    /// Sentence received so far, not including the leading `$` or the trailing CR LF.
+    /// A sentence is at most 82 characters including those.
+    buf: ArrayVec<u8, 79>,
+    /// Whether a `$` has been received, and the end of its sentence hasn't
+    in_sentence: bool
+}
+
+impl NmeaParser {
+    pub fn new() -> Self {
+        Self {
+            buf: ArrayVec::new(),
+            in_sentence: false
+        }
+    }
+
+    /// Feed in a received byte. Returns the sentence (or an error) once one is complete. Sentences
+    /// of types that aren't parsed are skipped.
+    pub fn parse_from_byte(&mut self, byte: u8) -> Option<Result<NmeaSentence, NmeaError>> {
+        match byte {
+            // Start of a new sentence, throwing away any incomplete one
+            b'$' => {
+                self.buf.clear();
+                self.in_sentence = true;
+                None
+            }
+            // Anything outside a sentence (e.g. noise while the receiver powers up) is ignored
+            _ if !self.in_sentence => None,
+            b'\r' => None,
+            b'\n' => {
+                self.in_sentence = false;
+                parse_sentence(&self.buf).transpose()
+            }
+            // Sentences are printable ASCII only
+            0x20..=0x7e => {
+                if self.buf.try_push(byte).is_err() {
+                    self.in_sentence = false;
+                    return Some(Err(NmeaError::TooLong));
+                }
+                None
+            }
+            _ => {
+                self.in_sentence = false;
+                Some(Err(NmeaError::UnexpectedCharacter))
+            }
+        }
+    }
+}
+
+/// NMEA 0183 parse error
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NmeaError {
+    /// Unexpected character
+    UnexpectedCharacter,
+    /// Sentence longer than the maximum of 82 characters
+    TooLong,
+    /// No `*` followed by a checksum at the end
+    MissingChecksum,
+    /// Checksum doesn't match the sentence
+    BadChecksum,
+    /// A field is missing or can't be parsed
+    InvalidField
+}
+
+/// A latitude or longitude
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Coord {
+    hemisphere: bool, // pos = true
+    degrees: u8, // 0-90 or 0-180
+    minutes: u8, // 0-60
+    frac_minutes: u16 // 1/10000 minutes
+}
+
+impl Coord {
+    /// Signed angle in millionths of a degree. North and east are positive.
+    pub fn micro_degrees(&self) -> i32 {
+        let minutes = self.minutes as i32 * 10_000 + self.frac_minutes as i32;
+        // minutes / 10000 / 60 * 1000000
+        let abs = self.degrees as i32 * 1_000_000 + minutes * 10 / 6;
+        if self.hemisphere { abs } else { -abs }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FixType {
+    Invalid = 0,
+    Autonomous = 1,
+    Dgps = 2,
+    Pps = 3,
+    Rtk = 4,
+    RtkFloat = 5,
+    Estimated = 6,
+    Manual = 7,
+    Simulation = 8,
+    Waas = 9
+}
+
+impl FixType {
+    fn from_u8(n: u8) -> Option<Self> {
+        Some(match n {
+            0 => Self::Invalid,
+            1 => Self::Autonomous,
+            2 => Self::Dgps,
+            3 => Self::Pps,
+            4 => Self::Rtk,
+            5 => Self::RtkFloat,
+            6 => Self::Estimated,
+            7 => Self::Manual,
+            8 => Self::Simulation,
+            9 => Self::Waas,
+            _ => return None
+        })
+    }
+
+    /// Whether this is an actual position fix
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, Self::Invalid)
+    }
+}
+
+/// A satellite in view, from GSV
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SatelliteInView {
+    pub prn: u8,
+    /// Degrees above the horizon, 0-90
+    pub elevation: Option<u8>,
+    /// Degrees clockwise from true north, 0-359
+    pub azimuth: Option<u16>,
+    /// Signal to noise ratio in dB-Hz, or `None` if it's not being tracked
+    pub snr: Option<u8>
+}
+
+/// Fix dimensions from GSA
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FixMode {
+    NoFix = 1,
+    Fix2d = 2,
+    Fix3d = 3
+}
+
+/// NMEA 0183 resulting sentence
+///
+/// Dilutions of precision are in tenths, saturating at 255 (25.5), which is too poor to be useful
+/// anyway. Fields the receiver left empty (e.g. position before a fix) are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NmeaSentence {
+    /// Fix Data
+    Gga {
+        time: Option<NaiveTime>,
+        latitude: Option<Coord>,
+        longitude: Option<Coord>,
+        fix_type: FixType,
+        satellites: u8,
+        hdop: u8,
+        /// Altitude above mean sea level in decimeters
+        altitude_dm: Option<i32>
+    },
+    /// Geographic Position
+    Gll {
+        latitude: Option<Coord>,
+        longitude: Option<Coord>,
+        time: Option<NaiveTime>,
+        valid: bool
+    },
+    /// Dilution of Precision and Satellites
+    Gsa {
+        fix_mode: FixMode,
+        /// PRNs of the satellites used in the fix
+        satellites: ArrayVec<u8, GSA_SATELLITES>,
+        pdop: u8,
+        hdop: u8,
+        vdop: u8
+    },
+    /// Satellites in View. The list is split over several sentences, each with up to 4.
+    Gsv {
+        /// How many sentences the list is split over, and which one this is (starting at 1)
+        total: u8,
+        number: u8,
+        /// Total satellites in view
+        in_view: u8,
+        satellites: ArrayVec<SatelliteInView, GSV_SATELLITES>
+    },
+    /// Recommended Minimum data
+    Rmc {
+        time: Option<NaiveTime>,
+        date: Option<NaiveDate>,
+        valid: bool,
+        latitude: Option<Coord>,
+        longitude: Option<Coord>,
+        /// Speed over ground in hundredths of a knot
+        speed_cknots: Option<u32>,
+        /// Course over ground in tenths of a degree
+        course_ddeg: Option<u16>
+    }
+}
+
+/// Parse a sentence, not including the `$` or CR LF. Returns `None` for sentence types that
+/// aren't parsed.
+fn parse_sentence(buf: &[u8]) -> Result<Option<NmeaSentence>, NmeaError> {
+    // Checksum is the XOR of everything between `$` and `*`, as two hex digits
+    let star = buf.iter().rposition(|&b| b == b'*').ok_or(NmeaError::MissingChecksum)?;
+    let (body, checksum) = (&buf[..star], &buf[star + 1..]);
+    let expected = core::str::from_utf8(checksum).ok()
+        .filter(|s| s.len() == 2)
+        .and_then(|s| u8::from_str_radix(s, 16).ok())
+        .ok_or(NmeaError::MissingChecksum)?;
+    if body.iter().fold(0, |acc, &b| acc ^ b) != expected {
+        return Err(NmeaError::BadChecksum);
+    }
+
+    // Only printable ASCII gets into the buffer, so this can't fail
+    let body = core::str::from_utf8(body).map_err(|_| NmeaError::UnexpectedCharacter)?;
+    let mut fields = Fields(body.split(','));
+
+    // Address is a 2 character talker ID (GP, GN, GL, ...) then the sentence type. Proprietary
+    // sentences start with P instead, and aren't parsed.
+    let address = fields.next()?;
+    if address.len() != 5 || address.starts_with('P') {
+        return Ok(None);
+    }
+
+    Ok(Some(match &address[2..] {
+        "GGA" => NmeaSentence::Gga {
+            time: parse_time(fields.next()?)?,
+            latitude: parse_coord(fields.next()?, fields.next()?)?,
+            longitude: parse_coord(fields.next()?, fields.next()?)?,
+            fix_type: FixType::from_u8(parse_fixed(fields.next()?, 0)?.unwrap_or(0).min(255) as u8)
+                .ok_or(NmeaError::InvalidField)?,
+            satellites: parse_fixed(fields.next()?, 0)?.unwrap_or(0).min(255) as u8,
+            hdop: parse_dop(fields.next()?)?,
+            altitude_dm: parse_signed_fixed(fields.next()?, 1)?
+        },
+        "GLL" => NmeaSentence::Gll {
+            latitude: parse_coord(fields.next()?, fields.next()?)?,
+            longitude: parse_coord(fields.next()?, fields.next()?)?,
+            time: parse_time(fields.next()?)?,
+            valid: fields.next()? == "A"
+        },
+        "GSA" => {
+            // Selection mode (manual/automatic), not needed
+            fields.next()?;
+            let fix_mode = match fields.next()? {
+                "2" => FixMode::Fix2d,
+                "3" => FixMode::Fix3d,
+                _ => FixMode::NoFix
+            };
+            let mut satellites = ArrayVec::new();
+            for _ in 0..GSA_SATELLITES {
+                if let Some(prn) = parse_fixed(fields.next()?, 0)? {
+                    satellites.push(prn.min(255) as u8);
+                }
+            }
+            NmeaSentence::Gsa {
+                fix_mode,
+                satellites,
+                pdop: parse_dop(fields.next()?)?,
+                hdop: parse_dop(fields.next()?)?,
+                vdop: parse_dop(fields.next()?)?
+            }
+        },
+        "GSV" => {
+            let total = parse_fixed(fields.next()?, 0)?.ok_or(NmeaError::InvalidField)?.min(255) as u8;
+            let number = parse_fixed(fields.next()?, 0)?.ok_or(NmeaError::InvalidField)?.min(255) as u8;
+            let in_view = parse_fixed(fields.next()?, 0)?.unwrap_or(0).min(255) as u8;
+            let mut satellites = ArrayVec::new();
+            // The last sentence has fewer satellites, and may have a signal ID after them (NMEA 4.1)
+            while satellites.len() < GSV_SATELLITES {
+                let Ok(prn) = fields.next() else {
+                    break;
+                };
+                // A single field left over is the signal ID, not the start of another satellite
+                let Ok(elevation) = fields.next() else {
+                    break;
+                };
+                let (azimuth, snr) = (fields.next()?, fields.next()?);
+                let Some(prn) = parse_fixed(prn, 0)? else {
+                    continue;
+                };
+                satellites.push(SatelliteInView {
+                    prn: prn.min(255) as u8,
+                    elevation: parse_fixed(elevation, 0)?.map(|e| e.min(90) as u8),
+                    azimuth: parse_fixed(azimuth, 0)?.map(|a| (a % 360) as u16),
+                    snr: parse_fixed(snr, 0)?.map(|s| s.min(99) as u8)
+                });
+            }
+            NmeaSentence::Gsv {
+                total,
+                number,
+                in_view,
+                satellites
+            }
+        },
+        "RMC" => NmeaSentence::Rmc {
+            time: parse_time(fields.next()?)?,
+            valid: fields.next()? == "A",
+            latitude: parse_coord(fields.next()?, fields.next()?)?,
+            longitude: parse_coord(fields.next()?, fields.next()?)?,
+            speed_cknots: parse_fixed(fields.next()?, 2)?,
+            course_ddeg: parse_fixed(fields.next()?, 1)?.map(|c| c.min(3599) as u16),
+            date: parse_date(fields.next()?)?
+        },
+        _ => return Ok(None)
+    }))
+}
+
+/// Comma-separated fields, where running out is an error
+struct Fields<'a>(core::str::Split<'a, char>);
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> Result<&'a str, NmeaError> {
+        self.0.next().ok_or(NmeaError::InvalidField)
+    }
+}
+
+/// Parse a decimal number into fixed point with `decimals` decimal places, e.g. "12.345" with 2
+/// decimals is 1234. Extra decimal places are truncated. Empty fields are `None`.
+fn parse_fixed(s: &str, decimals: u32) -> Result<Option<u32>, NmeaError> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if int.is_empty() && frac.is_empty() {
+        return Err(NmeaError::InvalidField);
+    }
+
+    let mut value: u32 = 0;
+    let digits = int.bytes().chain(frac.bytes().chain(core::iter::repeat(b'0')).take(decimals as usize));
+    for b in digits {
+        if !b.is_ascii_digit() {
+            return Err(NmeaError::InvalidField);
+        }
+        value = value.checked_mul(10)
+            .and_then(|v| v.checked_add((b - b'0') as u32))
+            .ok_or(NmeaError::InvalidField)?;
+    }
+    // Still check the truncated digits are digits
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(NmeaError::InvalidField);
+    }
+    Ok(Some(value))
+}
+
+/// [`parse_fixed()`], allowing a leading minus sign
+fn parse_signed_fixed(s: &str, decimals: u32) -> Result<Option<i32>, NmeaError> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s)
+    };
+    match parse_fixed(s, decimals)? {
+        Some(value) => {
+            let value = i32::try_from(value).map_err(|_| NmeaError::InvalidField)?;
+            Ok(Some(if negative { -value } else { value }))
+        }
+        None => Ok(None)
+    }
+}
+
+/// Dilution of precision in tenths, saturating. Missing values are treated as the worst.
+fn parse_dop(s: &str) -> Result<u8, NmeaError> {
+    Ok(parse_fixed(s, 1)?.unwrap_or(u32::MAX).min(255) as u8)
+}
+
+/// Time as `hhmmss` with optional fractional seconds
+fn parse_time(s: &str) -> Result<Option<NaiveTime>, NmeaError> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    if s.len() < 6 {
+        return Err(NmeaError::InvalidField);
+    }
+    let (hms, frac) = s.split_at(6);
+    let hms = parse_fixed(hms, 0)?.ok_or(NmeaError::InvalidField)?;
+    let millis = if frac.is_empty() {
+        0
+    }
+    else if frac.starts_with('.') {
+        parse_fixed(frac, 3)?.unwrap_or(0)
+    }
+    else {
+        return Err(NmeaError::InvalidField);
+    };
+    NaiveTime::from_hms_milli_opt(hms / 10_000, hms / 100 % 100, hms % 100, millis)
+        .map(Some)
+        .ok_or(NmeaError::InvalidField)
+}
+
+/// Date as `ddmmyy`, assumed to be in 2000-2099
+fn parse_date(s: &str) -> Result<Option<NaiveDate>, NmeaError> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    if s.len() != 6 {
+        return Err(NmeaError::InvalidField);
+    }
+    let dmy = parse_fixed(s, 0)?.ok_or(NmeaError::InvalidField)?;
+    NaiveDate::from_ymd_opt(2000 + (dmy % 100) as i32, dmy / 100 % 100, dmy / 10_000)
+        .map(Some)
+        .ok_or(NmeaError::InvalidField)
+}
+
+/// Latitude (`ddmm.mmmm`) or longitude (`dddmm.mmmm`) and its hemisphere (N/S/E/W)
+fn parse_coord(value: &str, hemisphere: &str) -> Result<Option<Coord>, NmeaError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let hemisphere = match hemisphere {
+        "N" | "E" => true,
+        "S" | "W" => false,
+        _ => return Err(NmeaError::InvalidField)
+    };
+    // Minutes are the two digits before the decimal point, degrees are everything before those
+    let point = value.find('.').unwrap_or(value.len());
+    if point < 3 {
+        return Err(NmeaError::InvalidField);
+    }
+    let degrees = parse_fixed(&value[..point - 2], 0)?.ok_or(NmeaError::InvalidField)?;
+    let minutes = parse_fixed(&value[point - 2..], 4)?.ok_or(NmeaError::InvalidField)?;
+    if degrees > 180 || minutes >= 60 * 10_000 {
+        return Err(NmeaError::InvalidField);
+    }
+    Ok(Some(Coord {
+        hemisphere,
+        degrees: degrees as u8,
+        minutes: (minutes / 10_000) as u8,
+        frac_minutes: (minutes % 10_000) as u16
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed a sentence through the parser, returning the result at the end of it
+    fn parse(sentence: &str) -> Option<Result<NmeaSentence, NmeaError>> {
+        let mut parser = NmeaParser::new();
+        let mut result = None;
+        for &b in sentence.as_bytes().iter().chain(b"\r\n") {
+            if let Some(r) = parser.parse_from_byte(b) {
+                assert!(result.is_none(), "more than one result");
+                result = Some(r);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn gga() {
+        let sentence = parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47");
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Gga {
+            time: NaiveTime::from_hms_opt(12, 35, 19),
+            latitude: Some(Coord { hemisphere: true, degrees: 48, minutes: 7, frac_minutes: 380 }),
+            longitude: Some(Coord { hemisphere: true, degrees: 11, minutes: 31, frac_minutes: 0 }),
+            fix_type: FixType::Autonomous,
+            satellites: 8,
+            hdop: 9,
+            altitude_dm: Some(5454)
+        })));
+    }
+
+    #[test]
+    fn gga_no_fix() {
+        let sentence = parse("$GNGGA,001043.00,,,,,0,00,99.99,,,,,,*7E");
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Gga {
+            time: NaiveTime::from_hms_opt(0, 10, 43),
+            latitude: None,
+            longitude: None,
+            fix_type: FixType::Invalid,
+            satellites: 0,
+            hdop: 255,
+            altitude_dm: None
+        })));
+    }
+
+    #[test]
+    fn rmc() {
+        let sentence = parse("$GPRMC,081836.75,A,3751.65,S,14507.36,E,002.5,054.7,181026,011.3,E*4C");
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Rmc {
+            time: NaiveTime::from_hms_milli_opt(8, 18, 36, 750),
+            date: NaiveDate::from_ymd_opt(2026, 10, 18),
+            valid: true,
+            latitude: Some(Coord { hemisphere: false, degrees: 37, minutes: 51, frac_minutes: 6500 }),
+            longitude: Some(Coord { hemisphere: true, degrees: 145, minutes: 7, frac_minutes: 3600 }),
+            speed_cknots: Some(250),
+            course_ddeg: Some(547)
+        })));
+
+        let sentence = parse("$GNRMC,000102.00,V,,,,,,,,,,N*60");
+        assert!(matches!(sentence, Some(Ok(NmeaSentence::Rmc { valid: false, date: None, latitude: None, .. }))));
+    }
+
+    #[test]
+    fn gsa() {
+        let sentence = parse("$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39");
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Gsa {
+            fix_mode: FixMode::Fix3d,
+            satellites: [4, 5, 9, 12, 24].into_iter().collect(),
+            pdop: 25,
+            hdop: 13,
+            vdop: 21
+        })));
+    }
+
+    #[test]
+    fn gll() {
+        let sentence = parse("$GPGLL,4916.45,N,12311.12,W,225444,A,*1D");
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Gll {
+            latitude: Some(Coord { hemisphere: true, degrees: 49, minutes: 16, frac_minutes: 4500 }),
+            longitude: Some(Coord { hemisphere: false, degrees: 123, minutes: 11, frac_minutes: 1200 }),
+            time: NaiveTime::from_hms_opt(22, 54, 44),
+            valid: true
+        })));
+    }
+
+    #[test]
+    fn gsv() {
+        let sentence = parse("$GPGSV,3,3,11,22,42,067,42,24,14,311,43,27,05,244,00*4D");
+        let sat = |prn, elevation, azimuth, snr| SatelliteInView {
+            prn,
+            elevation: Some(elevation),
+            azimuth: Some(azimuth),
+            snr
+        };
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Gsv {
+            total: 3,
+            number: 3,
+            in_view: 11,
+            satellites: [sat(22, 42, 67, Some(42)), sat(24, 14, 311, Some(43)), sat(27, 5, 244, Some(0))]
+                .into_iter().collect()
+        })));
+
+        // Position not known yet
+        let sentence = parse("$GPGSV,1,1,03,12,,,35,25,80,,*70");
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Gsv {
+            total: 1,
+            number: 1,
+            in_view: 3,
+            satellites: [
+                SatelliteInView { prn: 12, elevation: None, azimuth: None, snr: Some(35) },
+                SatelliteInView { prn: 25, elevation: Some(80), azimuth: None, snr: None }
+            ].into_iter().collect()
+        })));
+
+        // NMEA 4.1 signal ID after a short last sentence
+        let sentence = parse("$GPGSV,3,3,09,22,42,067,42,1*5C");
+        assert_eq!(sentence, Some(Ok(NmeaSentence::Gsv {
+            total: 3,
+            number: 3,
+            in_view: 9,
+            satellites: [sat(22, 42, 67, Some(42))].into_iter().collect()
+        })));
+    }
+
+    #[test]
+    fn unsupported_skipped() {
+        assert_eq!(parse("$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48"), None);
+        assert_eq!(parse("$PMTK001,161,3*36"), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("$GPGLL,4916.45,N,12311.12,W,225444,A,*1E"), Some(Err(NmeaError::BadChecksum)));
+        assert_eq!(parse("$GPGLL,4916.45,N,12311.12,W,225444,A,"), Some(Err(NmeaError::MissingChecksum)));
+        assert_eq!(parse("$GPGLL,4916.45,N*3B"), Some(Err(NmeaError::InvalidField)));
+        let long = ["$GPGGA", &",0".repeat(40)].concat();
+        assert_eq!(parse(&long), Some(Err(NmeaError::TooLong)));
+        assert_eq!(parse("$GPGGA\x01"), Some(Err(NmeaError::UnexpectedCharacter)));
+    }
+
+    #[test]
+    fn recovers_after_garbage() {
+        let mut parser = NmeaParser::new();
+        let input = b"\xff\x00junk$GPGL$GPGLL,4916.45,N,12311.12,W,225444,A,*1D\r\n";
+        let results: Vec<_> = input.iter().filter_map(|&b| parser.parse_from_byte(b)).collect();
+        assert!(matches!(results[..], [Ok(NmeaSentence::Gll { valid: true, .. })]));
+    }
+
+    #[test]
+    fn micro_degrees() {
+        let coord = Coord { hemisphere: false, degrees: 37, minutes: 51, frac_minutes: 6500 };
+        assert_eq!(coord.micro_degrees(), -37_860_833);
+    }
+}
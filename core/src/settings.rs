@@ -0,0 +1,434 @@
+//! User settings, and their layout in EEPROM.
+//!
+//! Settings are stored as a record of 32-bit words: a header with a magic number, layout version
+//! and payload length, a sequence number, the payload itself, and a CRC-32 over all of that. The
+//! payload layout is versioned so that settings saved by older firmware can be migrated instead of
+//! thrown away.
+
+use log::LevelFilter;
+
+
+/// Identifies a settings record ("SW")
+const MAGIC: u16 = 0x5753;
+/// Current payload layout version
+pub const VERSION: u8 = 4;
+/// Length of the current payload in bytes
+const PAYLOAD_LEN: usize = 25;
+/// Length of the version 1 payload in bytes
+const PAYLOAD_LEN_V1: usize = 22;
+/// Length of the version 2 payload in bytes
+const PAYLOAD_LEN_V2: usize = 23;
+/// Length of the version 3 payload in bytes
+const PAYLOAD_LEN_V3: usize = 24;
+/// Largest payload a record can hold, in words. Leaves room for later layouts to grow.
+const MAX_PAYLOAD_WORDS: usize = 12;
+/// Size of a record in words: header, sequence number, payload and CRC
+pub const RECORD_WORDS: usize = 2 + MAX_PAYLOAD_WORDS + 1;
+
+/// Number of alarms that can be set
+pub const ALARM_COUNT: usize = 4;
+
+/// Level filters, indexed by their value as `usize`
+const LEVEL_FILTERS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace
+];
+
+/// Convert a level filter stored as a number back, e.g. when loading settings
+pub fn level_filter_from_u8(n: u8) -> Option<LevelFilter> {
+    LEVEL_FILTERS.get(n as usize).copied()
+}
+
+/// Why a settings record couldn't be loaded, from least to most interesting
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SettingsError {
+    /// Nothing has been saved
+    Blank,
+    /// The record is damaged, e.g. by losing power while saving
+    Corrupt,
+    /// The record holds values that are out of range
+    Invalid,
+    /// The record was saved by newer firmware, with a layout this doesn't know
+    UnsupportedVersion(u8)
+}
+
+/// Units to show distances and speeds in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Units {
+    Metric,
+    Imperial
+}
+
+/// Which watch face the clock shows
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockFace {
+    /// Time in small text
+    Small,
+    /// Large digits filling the screen, with the date
+    Large,
+    /// Hour, minute and second hands
+    Analog
+}
+
+/// A daily alarm
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Alarm {
+    pub hour: u8,
+    pub minute: u8,
+    /// Days the alarm goes off on, bit 0 = Monday through bit 6 = Sunday
+    pub days: u8,
+    pub enabled: bool
+}
+impl Default for Alarm {
+    fn default() -> Self {
+        Self {
+            hour: 7,
+            minute: 0,
+            days: 0b0011111, // weekdays
+            enabled: false
+        }
+    }
+}
+
+/// Display preferences
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct DisplaySettings {
+    /// Draw white on black
+    pub inverted: bool,
+    /// Clockwise rotation in quarter turns (0-3)
+    pub rotation: u8
+}
+
+/// Settings that persist across resets and battery swaps
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Offset of local time from UTC, in minutes
+    pub utc_offset_min: i16,
+    pub alarms: [Alarm; ALARM_COUNT],
+    pub units: Units,
+    /// How often to log a track point while recording, in seconds
+    pub log_interval_s: u16,
+    pub display: DisplaySettings,
+    /// Log level for targets without their own filter
+    pub log_level: LevelFilter,
+    pub face: ClockFace,
+    /// ID of the UI mode to show, as from `UiMode::id()` in the firmware
+    pub mode: u8
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            utc_offset_min: 0,
+            alarms: [Alarm::default(); ALARM_COUNT],
+            units: Units::Metric,
+            log_interval_s: 5,
+            display: DisplaySettings::default(),
+            log_level: LevelFilter::Info,
+            face: ClockFace::Large,
+            mode: 0
+        }
+    }
+}
+
+impl Settings {
+    /// Serialize into a record with the given sequence number
+    pub fn to_record(self, seq: u32) -> [u32; RECORD_WORDS] {
+        make_record(VERSION, &self.encode(), seq)
+    }
+
+    /// Deserialize a record, migrating older layouts. Returns the sequence number and settings.
+    pub fn from_record(record: &[u32; RECORD_WORDS]) -> Result<(u32, Self), SettingsError> {
+        let header = record[0];
+        if header as u16 != MAGIC {
+            // Erased EEPROM reads as zeros
+            return Err(if record.iter().all(|&w| w == 0) {
+                SettingsError::Blank
+            }
+            else {
+                SettingsError::Corrupt
+            });
+        }
+        let version = (header >> 16) as u8;
+        let len = (header >> 24) as usize;
+        if len > MAX_PAYLOAD_WORDS * 4 {
+            return Err(SettingsError::Corrupt);
+        }
+        let crc_index = crc_index(len);
+        if crc32(&record[..crc_index]) != record[crc_index] {
+            return Err(SettingsError::Corrupt);
+        }
+        if version == 0 || version > VERSION {
+            return Err(SettingsError::UnsupportedVersion(version));
+        }
+
+        let mut payload = [0u8; MAX_PAYLOAD_WORDS * 4];
+        for (chunk, word) in payload.chunks_mut(4).zip(&record[2..crc_index]) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let settings = Self::decode(version, &payload[..len]).ok_or(SettingsError::Invalid)?;
+        Ok((record[1], settings))
+    }
+
+    /// Serialize into the current payload layout
+    fn encode(&self) -> [u8; PAYLOAD_LEN] {
+        let mut buf = [0u8; PAYLOAD_LEN];
+        buf[0..2].copy_from_slice(&self.utc_offset_min.to_le_bytes());
+        for (chunk, alarm) in buf[2..18].chunks_mut(4).zip(&self.alarms) {
+            chunk.copy_from_slice(&[alarm.hour, alarm.minute, alarm.days, alarm.enabled as u8]);
+        }
+        buf[18] = match self.units {
+            Units::Metric => 0,
+            Units::Imperial => 1
+        };
+        buf[19] = self.display.inverted as u8 | (self.display.rotation & 0b11) << 1;
+        buf[20..22].copy_from_slice(&self.log_interval_s.to_le_bytes());
+        buf[22] = self.log_level as usize as u8;
+        buf[23] = match self.face {
+            ClockFace::Small => 0,
+            ClockFace::Large => 1,
+            ClockFace::Analog => 2
+        };
+        buf[24] = self.mode;
+        buf
+    }
+
+    /// Deserialize a payload of the given layout version. Older layouts are migrated by filling
+    /// in anything they lack with defaults.
+    fn decode(version: u8, buf: &[u8]) -> Option<Self> {
+        match version {
+            1 => Self::decode_v1(buf),
+            2 => Self::decode_v2(buf),
+            3 => Self::decode_v3(buf),
+            4 => Self::decode_v4(buf),
+            _ => None
+        }
+    }
+
+    /// Version 4: version 3 plus the UI mode
+    fn decode_v4(buf: &[u8]) -> Option<Self> {
+        if buf.len() != PAYLOAD_LEN {
+            return None;
+        }
+        let mut settings = Self::decode_v3(&buf[..PAYLOAD_LEN_V3])?;
+        // Not checked here since the modes are defined by the firmware; it ignores unknown IDs
+        settings.mode = buf[24];
+        Some(settings)
+    }
+
+    /// Version 3: version 2 plus the clock face
+    fn decode_v3(buf: &[u8]) -> Option<Self> {
+        if buf.len() != PAYLOAD_LEN_V3 {
+            return None;
+        }
+        let mut settings = Self::decode_v2(&buf[..PAYLOAD_LEN_V2])?;
+        settings.face = match buf[23] {
+            0 => ClockFace::Small,
+            1 => ClockFace::Large,
+            2 => ClockFace::Analog,
+            _ => return None
+        };
+        Some(settings)
+    }
+
+    /// Version 2: version 1 plus the log level
+    fn decode_v2(buf: &[u8]) -> Option<Self> {
+        if buf.len() != PAYLOAD_LEN_V2 {
+            return None;
+        }
+        let mut settings = Self::decode_v1(&buf[..PAYLOAD_LEN_V1])?;
+        settings.log_level = level_filter_from_u8(buf[22])?;
+        Some(settings)
+    }
+
+    fn decode_v1(buf: &[u8]) -> Option<Self> {
+        if buf.len() != PAYLOAD_LEN_V1 {
+            return None;
+        }
+
+        let utc_offset_min = i16::from_le_bytes([buf[0], buf[1]]);
+        // UTC-12:00 to UTC+14:00
+        if !(-12 * 60..=14 * 60).contains(&utc_offset_min) {
+            return None;
+        }
+
+        let mut alarms = [Alarm::default(); ALARM_COUNT];
+        for (alarm, chunk) in alarms.iter_mut().zip(buf[2..18].chunks(4)) {
+            if chunk[0] >= 24 || chunk[1] >= 60 || chunk[2] & 0x80 != 0 || chunk[3] > 1 {
+                return None;
+            }
+            *alarm = Alarm {
+                hour: chunk[0],
+                minute: chunk[1],
+                days: chunk[2],
+                enabled: chunk[3] == 1
+            };
+        }
+
+        let units = match buf[18] {
+            0 => Units::Metric,
+            1 => Units::Imperial,
+            _ => return None
+        };
+        if buf[19] & !0b111 != 0 {
+            return None;
+        }
+        let display = DisplaySettings {
+            inverted: buf[19] & 1 != 0,
+            rotation: (buf[19] >> 1) & 0b11
+        };
+        let log_interval_s = u16::from_le_bytes([buf[20], buf[21]]);
+        if log_interval_s == 0 {
+            return None;
+        }
+
+        Some(Self {
+            utc_offset_min,
+            alarms,
+            units,
+            log_interval_s,
+            display,
+            ..Self::default()
+        })
+    }
+}
+
+/// Build a record from a payload
+fn make_record(version: u8, payload: &[u8], seq: u32) -> [u32; RECORD_WORDS] {
+    let mut record = [0u32; RECORD_WORDS];
+    record[0] = MAGIC as u32 | (version as u32) << 16 | (payload.len() as u32) << 24;
+    record[1] = seq;
+    for (word, chunk) in record[2..].iter_mut().zip(payload.chunks(4)) {
+        let mut bytes = [0u8; 4];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        *word = u32::from_le_bytes(bytes);
+    }
+    let crc_index = crc_index(payload.len());
+    record[crc_index] = crc32(&record[..crc_index]);
+    record
+}
+
+/// Index of the CRC word in a record with a payload of `len` bytes
+fn crc_index(len: usize) -> usize {
+    2 + len.div_ceil(4)
+}
+
+/// CRC-32 (IEEE) over words, taken as little-endian bytes. Bitwise since it only runs when
+/// settings or waypoints are loaded or saved.
+pub(crate) fn crc32(words: &[u32]) -> u32 {
+    let mut crc = !0u32;
+    for byte in words.iter().flat_map(|w| w.to_le_bytes()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom() -> Settings {
+        let mut settings = Settings {
+            utc_offset_min: -300,
+            units: Units::Imperial,
+            log_interval_s: 30,
+            display: DisplaySettings { inverted: true, rotation: 3 },
+            log_level: LevelFilter::Trace,
+            face: ClockFace::Analog,
+            mode: 2,
+            ..Settings::default()
+        };
+        settings.alarms[2] = Alarm { hour: 23, minute: 59, days: 0b1100000, enabled: true };
+        settings
+    }
+
+    #[test]
+    fn round_trip() {
+        for settings in [Settings::default(), custom()] {
+            let record = settings.to_record(42);
+            assert_eq!(Settings::from_record(&record), Ok((42, settings)));
+        }
+    }
+
+    #[test]
+    fn blank_rejected() {
+        // Erased EEPROM reads as zeros
+        assert_eq!(Settings::from_record(&[0; RECORD_WORDS]), Err(SettingsError::Blank));
+        assert_eq!(Settings::from_record(&[!0; RECORD_WORDS]), Err(SettingsError::Corrupt));
+    }
+
+    #[test]
+    fn corruption_rejected() {
+        let record = custom().to_record(7);
+        for word in 0..crc_index(PAYLOAD_LEN) + 1 {
+            let mut corrupt = record;
+            corrupt[word] ^= 1 << (word % 32);
+            assert_eq!(Settings::from_record(&corrupt), Err(SettingsError::Corrupt), "flipped bit in word {}", word);
+        }
+    }
+
+    #[test]
+    fn unknown_version_rejected() {
+        let record = make_record(VERSION + 1, &custom().encode(), 1);
+        assert_eq!(Settings::from_record(&record), Err(SettingsError::UnsupportedVersion(VERSION + 1)));
+    }
+
+    #[test]
+    fn migrate_v1() {
+        // Each version so far only adds fields to the end
+        let settings = custom();
+        let record = make_record(1, &settings.encode()[..PAYLOAD_LEN_V1], 3);
+        let expected = Settings {
+            log_level: Settings::default().log_level,
+            face: Settings::default().face,
+            mode: Settings::default().mode,
+            ..settings
+        };
+        assert_eq!(Settings::from_record(&record), Ok((3, expected)));
+    }
+
+    #[test]
+    fn migrate_v2() {
+        let settings = custom();
+        let record = make_record(2, &settings.encode()[..PAYLOAD_LEN_V2], 4);
+        let expected = Settings { face: Settings::default().face, mode: Settings::default().mode, ..settings };
+        assert_eq!(Settings::from_record(&record), Ok((4, expected)));
+    }
+
+    #[test]
+    fn migrate_v3() {
+        let settings = custom();
+        let record = make_record(3, &settings.encode()[..PAYLOAD_LEN_V3], 5);
+        let expected = Settings { mode: Settings::default().mode, ..settings };
+        assert_eq!(Settings::from_record(&record), Ok((5, expected)));
+    }
+
+    #[test]
+    fn invalid_enums_rejected() {
+        let mut payload = custom().encode();
+        payload[22] = 6;
+        assert_eq!(Settings::decode(VERSION, &payload), None);
+        payload = custom().encode();
+        payload[23] = 3; // face
+        assert_eq!(Settings::decode(VERSION, &payload), None);
+    }
+
+    #[test]
+    fn out_of_range_rejected() {
+        let mut payload = custom().encode();
+        payload[2] = 24; // alarm hour
+        assert_eq!(Settings::decode(VERSION, &payload), None);
+    }
+
+    #[test]
+    fn crc_check_value() {
+        // Standard CRC-32 of "1234"
+        assert_eq!(crc32(&[u32::from_le_bytes(*b"1234")]), 0x9BE3_E0A3);
+    }
+}
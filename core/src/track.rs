@@ -0,0 +1,223 @@
+//! The breadcrumb trail recorded while moving, and simplifying it into a route back to the start.
+//!
+//! The trail is kept in RAM with a fixed number of points. Rather than stopping or dropping the
+//! start when it fills up, every other point is thrown away and from then on points are kept half
+//! as often, so it always covers the whole trip, just more coarsely the longer it gets.
+
+use arrayvec::ArrayVec;
+
+use crate::geo::Position;
+use crate::trig;
+
+/// Most points kept in the trail
+pub const TRACK_POINTS: usize = 256;
+/// Most points in a simplified route
+pub const ROUTE_POINTS: usize = 32;
+/// How far the trail may stray from a simplified route at first, in meters. Doubled until the
+/// route fits in [`ROUTE_POINTS`].
+const ROUTE_TOLERANCE_M: u32 = 10;
+
+/// A recorded trail of positions, oldest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+    points: ArrayVec<Position, TRACK_POINTS>,
+    /// Only every `stride`th point pushed is kept. Doubles each time the trail fills up.
+    stride: u32,
+    /// Points pushed since the last one that was kept
+    skipped: u32
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            points: ArrayVec::new(),
+            stride: 1,
+            skipped: 0
+        }
+    }
+}
+
+impl Track {
+    pub fn points(&self) -> &[Position] {
+        &self.points
+    }
+
+    /// Forget the trail, e.g. to start recording a new one
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Add a position to the end of the trail, thinning it out if it's full
+    pub fn push(&mut self, position: Position) {
+        self.skipped += 1;
+        if !self.points.is_empty() && self.skipped < self.stride {
+            return;
+        }
+        self.skipped = 0;
+
+        if self.points.is_full() {
+            // Keep the even points, which includes the start
+            let mut i = 0;
+            self.points.retain(|_| {
+                i += 1;
+                i % 2 == 1
+            });
+            self.stride *= 2;
+        }
+        self.points.push(position);
+    }
+
+    /// The trail simplified into a route from the newest point back to the start, leaving out the
+    /// newest point itself. Empty if nothing has been recorded.
+    pub fn route_back(&self) -> ArrayVec<Position, ROUTE_POINTS> {
+        let Some((newest, rest)) = self.points.split_last() else {
+            return ArrayVec::new();
+        };
+        if rest.is_empty() {
+            // Haven't moved on from the start
+            return [*newest].into_iter().collect();
+        }
+
+        let mut tolerance = ROUTE_TOLERANCE_M;
+        loop {
+            let keep = simplify(&self.points, tolerance);
+            let kept = keep.iter().filter(|&&k| k).count();
+            // Less one for the newest point
+            if kept - 1 <= ROUTE_POINTS {
+                return rest.iter().zip(&keep).rev()
+                    .filter(|(_, &k)| k)
+                    .map(|(&p, _)| p)
+                    .collect();
+            }
+            tolerance *= 2;
+        }
+    }
+}
+
+/// Simplify a trail with the Douglas–Peucker algorithm: keep the ends, then recursively keep the
+/// point furthest from the line between the ends if it's more than `tolerance_m` away. Returns
+/// which points are kept.
+///
+/// Uses an explicit stack of the ranges still to check rather than recursing, so stack use is
+/// bounded.
+fn simplify(points: &[Position], tolerance_m: u32) -> [bool; TRACK_POINTS] {
+    let mut keep = [false; TRACK_POINTS];
+    let Some(last) = points.len().min(TRACK_POINTS).checked_sub(1) else {
+        return keep;
+    };
+    keep[0] = true;
+    keep[last] = true;
+
+    // Each range splits into two only by keeping a point, so this can't overflow
+    let mut ranges = ArrayVec::<(u16, u16), TRACK_POINTS>::new();
+    ranges.push((0, last as u16));
+    while let Some((first, last)) = ranges.pop() {
+        let (first, last) = (first as usize, last as usize);
+        let furthest = (first + 1..last)
+            .map(|i| (i, distance_to_line_m(&points[i], &points[first], &points[last])))
+            .max_by_key(|&(_, d)| d);
+        if let Some((i, distance)) = furthest {
+            if distance > tolerance_m {
+                keep[i] = true;
+                ranges.push((first as u16, i as u16));
+                ranges.push((i as u16, last as u16));
+            }
+        }
+    }
+    keep
+}
+
+/// Distance from `point` to the line through `start` and `end`, in meters
+fn distance_to_line_m(point: &Position, start: &Position, end: &Position) -> u32 {
+    let (ax, ay) = start.offset_m(end);
+    let (px, py) = start.offset_m(point);
+    let (ax, ay, px, py) = (ax as i64, ay as i64, px as i64, py as i64);
+
+    let length = trig::isqrt((ax * ax + ay * ay) as u64) as i64;
+    if length == 0 {
+        return trig::isqrt((px * px + py * py) as u64) as u32;
+    }
+    // The cross product is the area of the parallelogram with the line as its base
+    ((ax * py - ay * px).abs() / length) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// About 1.1 m per step in either direction, near the equator
+    fn pos(x: i32, y: i32) -> Position {
+        Position { latitude: y * 10, longitude: x * 10 }
+    }
+
+    #[test]
+    fn straight_line_simplified_to_ends() {
+        let points: ArrayVec<_, 10> = (0..10).map(|i| pos(i * 100, i * 50)).collect();
+        let keep = simplify(&points, 5);
+        assert!(keep[0] && keep[9]);
+        assert_eq!(keep.iter().filter(|&&k| k).count(), 2);
+    }
+
+    #[test]
+    fn corner_kept() {
+        // East 1 km, then north 1 km, with a little wobble
+        let mut points = ArrayVec::<_, 21>::new();
+        for i in 0..=10 {
+            points.push(pos(i * 90, i % 2));
+        }
+        for i in 1..=10 {
+            points.push(pos(900 + i % 2, i * 90));
+        }
+        let keep = simplify(&points, 5);
+        let kept: ArrayVec<_, 21> = (0..21).filter(|&i| keep[i]).collect();
+        assert_eq!(kept.as_slice(), &[0, 10, 20]);
+    }
+
+    #[test]
+    fn route_back_reversed() {
+        let mut track = Track::default();
+        assert!(track.route_back().is_empty());
+        track.push(pos(0, 0));
+        assert_eq!(track.route_back().as_slice(), &[pos(0, 0)]);
+
+        for i in 1..=10 {
+            track.push(pos(i * 90, 0));
+        }
+        for i in 1..=10 {
+            track.push(pos(900, i * 90));
+        }
+        // Back via the corner, not including where we are now
+        assert_eq!(track.route_back().as_slice(), &[pos(900, 0), pos(0, 0)]);
+    }
+
+    #[test]
+    fn route_fits() {
+        // A zigzag that can't be simplified at the first tolerance
+        let mut track = Track::default();
+        for i in 0..TRACK_POINTS as i32 {
+            track.push(pos(i * 20, (i % 2) * 100));
+        }
+        let route = track.route_back();
+        assert!(!route.is_empty() && route.len() <= ROUTE_POINTS);
+        assert_eq!(route.last(), Some(&pos(0, 0)));
+    }
+
+    #[test]
+    fn thinned_when_full() {
+        let mut track = Track::default();
+        for i in 0..TRACK_POINTS as i32 {
+            track.push(pos(i, 0));
+        }
+        assert_eq!(track.points().len(), TRACK_POINTS);
+
+        // Half are thrown away, then every other push is kept
+        track.push(pos(1000, 0));
+        assert_eq!(track.points().len(), TRACK_POINTS / 2 + 1);
+        assert_eq!(track.points()[0], pos(0, 0));
+        assert_eq!(track.points()[1], pos(2, 0));
+        track.push(pos(1001, 0));
+        track.push(pos(1002, 0));
+        assert_eq!(track.points().last(), Some(&pos(1002, 0)));
+        assert_eq!(track.points().len(), TRACK_POINTS / 2 + 2);
+    }
+}
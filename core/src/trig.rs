@@ -0,0 +1,149 @@
+//! Integer trig for drawing, since there's no FPU.
+//!
+//! Angles are in whole degrees, clockwise from straight up (12 o'clock, or north), which is how
+//! both clock hands and compass bearings are measured.
+
+/// Scale of the values returned by [`sin`] and [`cos`]
+pub const ONE: i32 = 1024;
+
+/// `sin(i°) * ONE` for a quarter turn
+const SIN_QUARTER: [i16; 91] = [
+    0, 18, 36, 54, 71, 89, 107, 125, 143, 160,
+    178, 195, 213, 230, 248, 265, 282, 299, 316, 333,
+    350, 367, 384, 400, 416, 433, 449, 465, 481, 496,
+    512, 527, 543, 558, 573, 587, 602, 616, 630, 644,
+    658, 672, 685, 698, 711, 724, 737, 749, 761, 773,
+    784, 796, 807, 818, 828, 839, 849, 859, 868, 878,
+    887, 896, 904, 912, 920, 928, 935, 943, 949, 956,
+    962, 968, 974, 979, 984, 989, 994, 998, 1002, 1005,
+    1008, 1011, 1014, 1016, 1018, 1020, 1022, 1023, 1023, 1024,
+    1024
+];
+
+/// Sine of `degrees`, scaled by [`ONE`]
+pub fn sin(degrees: u32) -> i32 {
+    let degrees = degrees % 360;
+    let i = (degrees % 90) as usize;
+    match degrees / 90 {
+        0 => SIN_QUARTER[i] as i32,
+        1 => SIN_QUARTER[90 - i] as i32,
+        2 => -(SIN_QUARTER[i] as i32),
+        _ => -(SIN_QUARTER[90 - i] as i32)
+    }
+}
+
+/// Cosine of `degrees`, scaled by [`ONE`]
+pub fn cos(degrees: u32) -> i32 {
+    sin(degrees + 90)
+}
+
+/// Offset from the center of a point `radius` away at `degrees`, in screen coordinates (y down)
+pub fn polar(degrees: u32, radius: i32) -> (i32, i32) {
+    (
+        radius * sin(degrees) / ONE,
+        -radius * cos(degrees) / ONE
+    )
+}
+
+/// Direction of the vector (`x`, `y`) in degrees, where `y` is up (north) and `x` is right (east).
+/// Accurate to about a degree. (0, 0) is 0.
+pub fn atan2(x: i32, y: i32) -> u32 {
+    let (ax, ay) = (x.unsigned_abs() as u64, y.unsigned_abs() as u64);
+    if ax == 0 && ay == 0 {
+        return 0;
+    }
+    // Angle from the nearest axis in the first octant, found by walking the table until
+    // sin/cos reaches min/max
+    let (min, max) = (ax.min(ay), ax.max(ay));
+    let mut angle = 0;
+    while angle < 45 && max * sin(angle + 1) as u64 <= min * cos(angle + 1) as u64 {
+        angle += 1;
+    }
+    // Round to whichever neighbour is closer
+    if angle < 45 {
+        let below = min * cos(angle) as u64 - max * sin(angle) as u64;
+        let above = max * sin(angle + 1) as u64 - min * cos(angle + 1) as u64;
+        if above < below {
+            angle += 1;
+        }
+    }
+    // Angle from north within the quadrant
+    let angle = if ax > ay { 90 - angle } else { angle };
+
+    match (x >= 0, y >= 0) {
+        (true, true) => angle,
+        (true, false) => 180 - angle,
+        (false, false) => 180 + angle,
+        (false, true) => (360 - angle) % 360
+    }
+}
+
+/// Integer square root, rounded down
+pub fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    // Newton's method, starting above the root
+    let mut x = 1u64 << ((64 - n.leading_zeros()) / 2 + 1);
+    loop {
+        let next = (x + n / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quadrants() {
+        assert_eq!(sin(0), 0);
+        assert_eq!(sin(90), ONE);
+        assert_eq!(sin(180), 0);
+        assert_eq!(sin(270), -ONE);
+        assert_eq!(cos(0), ONE);
+        assert_eq!(cos(180), -ONE);
+        // Wraps around
+        assert_eq!(sin(367), sin(7));
+    }
+
+    #[test]
+    fn matches_float() {
+        for degrees in 0..360 {
+            let expected = ((degrees as f64).to_radians().sin() * ONE as f64).round() as i32;
+            assert!((sin(degrees) - expected).abs() <= 1, "sin({})", degrees);
+        }
+    }
+
+    #[test]
+    fn atan2_matches_float() {
+        for degrees in 0..360u32 {
+            let radians = (degrees as f64).to_radians();
+            let (x, y) = ((radians.sin() * 10_000.0) as i32, (radians.cos() * 10_000.0) as i32);
+            let diff = (atan2(x, y) as i32 - degrees as i32).rem_euclid(360);
+            assert!(diff <= 1 || diff >= 359, "atan2 at {}: {}", degrees, atan2(x, y));
+        }
+        assert_eq!(atan2(0, 0), 0);
+        assert_eq!(atan2(-5, 0), 270);
+    }
+
+    #[test]
+    fn isqrt_exact() {
+        for n in [0u64, 1, 2, 3, 4, 15, 16, 17, 99, 100, 1 << 40, u32::MAX as u64, u64::MAX] {
+            let root = isqrt(n);
+            assert!(root * root <= n, "isqrt({})", n);
+            assert!((root + 1).checked_mul(root + 1).is_none_or(|sq| sq > n), "isqrt({})", n);
+        }
+    }
+
+    #[test]
+    fn polar_directions() {
+        assert_eq!(polar(0, 10), (0, -10)); // 12 o'clock is up
+        assert_eq!(polar(90, 10), (10, 0)); // 3 o'clock is right
+        assert_eq!(polar(180, 10), (0, 10));
+        assert_eq!(polar(270, 10), (-10, 0));
+    }
+}
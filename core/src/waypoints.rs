@@ -0,0 +1,122 @@
+//! Saved waypoints, and their layout in EEPROM.
+//!
+//! Each waypoint has its own fixed-size record slot: a header with a magic number and the slot
+//! index, the position, the name, and a CRC-32 over all of that. An empty slot is left erased.
+
+use arrayvec::ArrayString;
+
+use crate::geo::Position;
+use crate::settings::crc32;
+
+/// Identifies a waypoint record ("WP")
+const MAGIC: u16 = 0x5057;
+/// Number of waypoints that can be saved
+pub const WAYPOINT_COUNT: usize = 16;
+/// Maximum length of a waypoint's name in bytes
+pub const NAME_LEN: usize = 8;
+/// Size of a record in words: header, latitude, longitude, name and CRC
+pub const RECORD_WORDS: usize = 3 + NAME_LEN / 4 + 1;
+
+/// A named position to navigate back to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Waypoint {
+    pub name: ArrayString<NAME_LEN>,
+    pub position: Position
+}
+
+/// Why a waypoint record couldn't be loaded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WaypointError {
+    /// Nothing has been saved in the slot
+    Blank,
+    /// The record is damaged, or belongs to another slot
+    Corrupt
+}
+
+impl Waypoint {
+    /// Serialize into a record for the given slot
+    pub fn to_record(self, slot: usize) -> [u32; RECORD_WORDS] {
+        let mut record = [0u32; RECORD_WORDS];
+        record[0] = MAGIC as u32 | (slot as u32) << 16;
+        record[1] = self.position.latitude as u32;
+        record[2] = self.position.longitude as u32;
+        let mut name = [0u8; NAME_LEN];
+        name[..self.name.len()].copy_from_slice(self.name.as_bytes());
+        for (word, chunk) in record[3..].iter_mut().zip(name.chunks(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        record[RECORD_WORDS - 1] = crc32(&record[..RECORD_WORDS - 1]);
+        record
+    }
+
+    /// Deserialize the record from the given slot
+    pub fn from_record(record: &[u32; RECORD_WORDS], slot: usize) -> Result<Self, WaypointError> {
+        // Erased EEPROM reads as zeros
+        if record.iter().all(|&w| w == 0) {
+            return Err(WaypointError::Blank);
+        }
+        if record[0] != MAGIC as u32 | (slot as u32) << 16
+            || crc32(&record[..RECORD_WORDS - 1]) != record[RECORD_WORDS - 1]
+        {
+            return Err(WaypointError::Corrupt);
+        }
+
+        let mut name = ArrayString::new();
+        let bytes = record[3..RECORD_WORDS - 1].iter().flat_map(|w| w.to_le_bytes());
+        for c in bytes.take_while(|&b| b != 0) {
+            // Names are only ever written as ASCII
+            if !c.is_ascii() {
+                return Err(WaypointError::Corrupt);
+            }
+            name.push(c as char);
+        }
+        Ok(Self {
+            name,
+            position: Position {
+                latitude: record[1] as i32,
+                longitude: record[2] as i32
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waypoint(name: &str) -> Waypoint {
+        Waypoint {
+            name: ArrayString::from(name).unwrap(),
+            position: Position { latitude: -33_856_784, longitude: 151_215_297 }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for name in ["", "WP01", "CAMPSITE"] {
+            let record = waypoint(name).to_record(5);
+            assert_eq!(Waypoint::from_record(&record, 5), Ok(waypoint(name)));
+        }
+    }
+
+    #[test]
+    fn blank_rejected() {
+        assert_eq!(Waypoint::from_record(&[0; RECORD_WORDS], 0), Err(WaypointError::Blank));
+    }
+
+    #[test]
+    fn corruption_rejected() {
+        let record = waypoint("HOME").to_record(2);
+        for word in 0..RECORD_WORDS {
+            let mut corrupt = record;
+            corrupt[word] ^= 1 << (word % 32);
+            assert_eq!(Waypoint::from_record(&corrupt, 2), Err(WaypointError::Corrupt), "flipped bit in word {}", word);
+        }
+    }
+
+    #[test]
+    fn wrong_slot_rejected() {
+        let record = waypoint("HOME").to_record(2);
+        assert_eq!(Waypoint::from_record(&record, 3), Err(WaypointError::Corrupt));
+    }
+}
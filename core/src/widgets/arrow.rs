@@ -0,0 +1,49 @@
+//! Arrows pointing in a direction, e.g. towards a waypoint.
+
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Triangle, PrimitiveStyle}
+};
+
+use super::Widget;
+use crate::trig;
+
+/// Angle either side of the tip that the back corners are at
+const BARB_ANGLE: u32 = 140;
+
+/// A filled arrowhead with a notched back, as big as fits, rotated to point `degrees` clockwise
+/// from the top
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Arrow {
+    pub degrees: u32
+}
+
+impl Arrow {
+    pub fn new(degrees: u32) -> Self {
+        Self {
+            degrees: degrees % 360
+        }
+    }
+}
+
+impl Widget for Arrow {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let center = area.center();
+        let radius = (area.size.width.min(area.size.height) / 2).saturating_sub(1) as i32;
+        let point = |degrees: u32, r: i32| {
+            let (x, y) = trig::polar(degrees % 360, r);
+            center + Point::new(x, y)
+        };
+
+        let tip = point(self.degrees, radius);
+        let left = point(self.degrees + 360 - BARB_ANGLE, radius);
+        let right = point(self.degrees + BARB_ANGLE, radius);
+        let notch = point(self.degrees + 180, radius / 3);
+        let style = PrimitiveStyle::with_fill(BinaryColor::On);
+        // Cannot error
+        let _ = Triangle::new(tip, left, notch).into_styled(style).draw(target);
+        let _ = Triangle::new(tip, notch, right).into_styled(style).draw(target);
+    }
+}
@@ -0,0 +1,103 @@
+//! A number in seven-segment digits, for values that should be readable at a glance.
+
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor
+};
+
+use super::Widget;
+use super::seven_segment::SevenSegment;
+
+/// Space between digits, as a fraction of the digit width
+const GAP_DIVISOR: u32 = 5;
+
+/// A number drawn as large as fits its area, right-aligned
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BigNumber {
+    pub value: u32,
+    /// Pad with leading zeros to at least this many digits
+    pub min_digits: u8
+}
+
+impl BigNumber {
+    pub fn new(value: u32) -> Self {
+        Self {
+            value,
+            min_digits: 1
+        }
+    }
+
+    pub fn zero_padded(self, min_digits: u8) -> Self {
+        Self {
+            min_digits,
+            ..self
+        }
+    }
+
+    /// How many digits will be drawn
+    fn digit_count(&self) -> u32 {
+        let mut count = 1;
+        let mut n = self.value / 10;
+        while n > 0 {
+            count += 1;
+            n /= 10;
+        }
+        count.max(self.min_digits as u32)
+    }
+}
+
+impl Widget for BigNumber {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let count = self.digit_count();
+
+        // Largest digits that fit both ways: width from the area's width (each digit plus a gap),
+        // limited by the height
+        let from_width = area.size.width * GAP_DIVISOR / (count * (GAP_DIVISOR + 1));
+        let digits = SevenSegment::with_width(from_width).fit_height(area.size.height);
+        // Digits have a minimum size, so can't always be made to fit
+        if digits.size.height > area.size.height {
+            return;
+        }
+        let gap = (digits.size.width / GAP_DIVISOR).max(1);
+
+        let y = (area.size.height - digits.size.height) as i32 / 2;
+        let mut x = area.size.width as i32 - digits.size.width as i32;
+        let mut n = self.value;
+        for _ in 0..count {
+            digits.draw_digit(target, Point::new(x, y), (n % 10) as u8);
+            n /= 10;
+            x -= (digits.size.width + gap) as i32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::{
+        mock_display::MockDisplay,
+        primitives::Rectangle
+    };
+
+    #[test]
+    fn zero_padded() {
+        let mut display = MockDisplay::new();
+        BigNumber::new(7).zero_padded(3).draw(&mut display.cropped(&Rectangle::new(Point::zero(), Size::new(36, 20))));
+
+        // 10 px wide digits with 2 px gaps, right-aligned and vertically centered
+        let mut expected = MockDisplay::new();
+        let digits = SevenSegment::with_width(10);
+        for (x, digit) in [(2, 0), (14, 0), (26, 7)] {
+            digits.draw_digit(&mut expected, Point::new(x, 1), digit);
+        }
+        display.assert_eq(&expected);
+    }
+
+    #[test]
+    fn too_short_draws_nothing() {
+        let mut display = MockDisplay::new();
+        BigNumber::new(42).draw(&mut display.cropped(&Rectangle::new(Point::zero(), Size::new(30, 4))));
+        assert_eq!(display, MockDisplay::new());
+    }
+}
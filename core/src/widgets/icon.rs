@@ -0,0 +1,39 @@
+//! Small bitmaps.
+
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    image::{Image, ImageRaw}
+};
+
+use super::Widget;
+
+/// A 1 bit per pixel bitmap, rows padded to whole bytes with the leftmost pixel in the MSB,
+/// centered in its area
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Icon {
+    pub data: &'static [u8],
+    pub size: Size
+}
+
+impl Icon {
+    pub const fn new(data: &'static [u8], size: Size) -> Self {
+        Self {
+            data,
+            size
+        }
+    }
+}
+
+impl Widget for Icon {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let raw = ImageRaw::<BinaryColor>::new(self.data, self.size.width);
+        let top_left = Point::new(
+            (area.size.width as i32 - self.size.width as i32) / 2,
+            (area.size.height as i32 - self.size.height as i32) / 2
+        );
+        // Cannot error
+        let _ = Image::new(&raw, top_left).draw(target);
+    }
+}
@@ -0,0 +1,122 @@
+//! Text.
+
+use arrayvec::ArrayString;
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    text::{Text, Alignment, Baseline, TextStyleBuilder}
+};
+
+use super::{Widget, Font};
+
+/// A line of text, up to `N` bytes, vertically centered in its area
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label<const N: usize> {
+    pub text: ArrayString<N>,
+    pub font: Font,
+    pub alignment: Alignment
+}
+
+impl<const N: usize> Label<N> {
+    /// Left-aligned text. Text that doesn't fit in `N` bytes is cut off.
+    pub fn new(text: &str, font: Font) -> Self {
+        let mut label = Self {
+            text: ArrayString::new(),
+            font,
+            alignment: Alignment::Left
+        };
+        label.set_text(text);
+        label
+    }
+
+    pub fn aligned(self, alignment: Alignment) -> Self {
+        Self {
+            alignment,
+            ..self
+        }
+    }
+
+    /// Replace the text, cutting it off at a character boundary if it doesn't fit
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        for c in text.chars() {
+            if self.text.try_push(c).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl<const N: usize> Widget for Label<N> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let x = match self.alignment {
+            Alignment::Left => 0,
+            Alignment::Center => area.size.width as i32 / 2,
+            Alignment::Right => area.size.width as i32 - 1
+        };
+        let text_style = TextStyleBuilder::new()
+            .alignment(self.alignment)
+            .baseline(Baseline::Middle)
+            .build();
+        // Cannot error
+        let _ = Text::with_text_style(
+            &self.text,
+            Point::new(x, area.size.height as i32 / 2),
+            self.font.style(BinaryColor::On),
+            text_style
+        ).draw(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::{
+        mock_display::MockDisplay,
+        primitives::Rectangle
+    };
+
+    /// Where the text ends up in a 40x20 area
+    fn drawn_area(alignment: Alignment) -> Rectangle {
+        let mut display = MockDisplay::new();
+        let label = Label::<8>::new("00", Font::Medium).aligned(alignment);
+        label.draw(&mut display.cropped(&Rectangle::new(Point::zero(), Size::new(40, 20))));
+        display.affected_area()
+    }
+
+    #[test]
+    fn alignment() {
+        let left = drawn_area(Alignment::Left);
+        let center = drawn_area(Alignment::Center);
+        let right = drawn_area(Alignment::Right);
+        // Same text, only moved sideways
+        assert_eq!(left.size, center.size);
+        assert_eq!(left.size, right.size);
+        assert_eq!(left.top_left.y, center.top_left.y);
+        assert_eq!(left.top_left.y, right.top_left.y);
+
+        // Two 6 px wide characters, each glyph 5 px with a column of space after it
+        assert_eq!(left.size.width, 11);
+        assert_eq!(left.top_left.x, 0);
+        let center_margins = (center.top_left.x, 40 - (center.top_left.x + 11));
+        assert!((center_margins.0 - center_margins.1).abs() <= 1, "margins {:?}", center_margins);
+        // Including the space after the last glyph, the text ends at the edge
+        assert_eq!(right.top_left.x + 12, 40);
+    }
+
+    #[test]
+    fn vertically_centered() {
+        let area = drawn_area(Alignment::Left);
+        let top = area.top_left.y;
+        let bottom = 20 - (top + area.size.height as i32);
+        assert!((top - bottom).abs() <= 1, "{} above, {} below", top, bottom);
+    }
+
+    #[test]
+    fn cut_off_at_char_boundary() {
+        let label = Label::<3>::new("a°b", Font::Small);
+        // '°' is 2 bytes, so 'b' doesn't fit
+        assert_eq!(label.text.as_str(), "a°");
+    }
+}
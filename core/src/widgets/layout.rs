@@ -0,0 +1,131 @@
+//! Splitting areas of the screen up between widgets.
+
+use embedded_graphics::{
+    prelude::*,
+    primitives::Rectangle
+};
+
+/// How much space an item in a [`Layout`] gets
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    /// Exactly this many pixels
+    Fixed(u32),
+    /// A share of what's left after the fixed items, in proportion to the weight
+    Fill(u32)
+}
+
+/// Splits an area into a row or column of smaller ones
+#[derive(Debug, Copy, Clone)]
+pub struct Layout {
+    area: Rectangle,
+    vertical: bool,
+    spacing: u32
+}
+
+impl Layout {
+    /// Stack items top to bottom
+    pub fn vertical(area: Rectangle) -> Self {
+        Self {
+            area,
+            vertical: true,
+            spacing: 0
+        }
+    }
+
+    /// Place items left to right
+    pub fn horizontal(area: Rectangle) -> Self {
+        Self {
+            area,
+            vertical: false,
+            spacing: 0
+        }
+    }
+
+    /// Leave a gap between items
+    pub fn spacing(self, spacing: u32) -> Self {
+        Self {
+            spacing,
+            ..self
+        }
+    }
+
+    /// Split the area between `N` items. If the fixed items don't fit, the later ones are cut off.
+    pub fn split<const N: usize>(&self, constraints: [Constraint; N]) -> [Rectangle; N] {
+        let total = if self.vertical { self.area.size.height } else { self.area.size.width };
+
+        let mut fixed = self.spacing * (N as u32).saturating_sub(1);
+        let mut weights = 0;
+        for constraint in constraints {
+            match constraint {
+                Constraint::Fixed(n) => fixed += n,
+                Constraint::Fill(weight) => weights += weight
+            }
+        }
+        let mut remaining = total.saturating_sub(fixed);
+
+        let mut offset = 0;
+        constraints.map(|constraint| {
+            let length = match constraint {
+                Constraint::Fixed(n) => n,
+                Constraint::Fill(weight) => {
+                    // Share out what's left, giving any rounding error to the last item
+                    let length = if weight == weights { remaining } else { remaining * weight / weights.max(1) };
+                    weights -= weight;
+                    remaining -= length;
+                    length
+                }
+            };
+            let length = length.min(total.saturating_sub(offset));
+            let rect = if self.vertical {
+                Rectangle::new(
+                    self.area.top_left + Point::new(0, offset as i32),
+                    Size::new(self.area.size.width, length)
+                )
+            }
+            else {
+                Rectangle::new(
+                    self.area.top_left + Point::new(offset as i32, 0),
+                    Size::new(length, self.area.size.height)
+                )
+            };
+            offset = (offset + length + self.spacing).min(total);
+            rect
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: u32, h: u32) -> Rectangle {
+        Rectangle::new(Point::new(x, y), Size::new(w, h))
+    }
+
+    #[test]
+    fn vertical() {
+        let layout = Layout::vertical(rect(0, 10, 100, 50));
+        let [a, b, c] = layout.split([Constraint::Fixed(10), Constraint::Fill(1), Constraint::Fixed(5)]);
+        assert_eq!(a, rect(0, 10, 100, 10));
+        assert_eq!(b, rect(0, 20, 100, 35));
+        assert_eq!(c, rect(0, 55, 100, 5));
+    }
+
+    #[test]
+    fn horizontal_weights_and_spacing() {
+        let layout = Layout::horizontal(rect(0, 0, 101, 20)).spacing(1);
+        let [a, b] = layout.split([Constraint::Fill(1), Constraint::Fill(2)]);
+        assert_eq!(a, rect(0, 0, 33, 20));
+        // Gets the rounding error
+        assert_eq!(b, rect(34, 0, 67, 20));
+    }
+
+    #[test]
+    fn overflow_is_cut_off() {
+        let layout = Layout::vertical(rect(0, 0, 10, 15));
+        let [a, b, c] = layout.split([Constraint::Fixed(10), Constraint::Fixed(10), Constraint::Fill(1)]);
+        assert_eq!(a, rect(0, 0, 10, 10));
+        assert_eq!(b, rect(0, 10, 10, 5));
+        assert_eq!(c, rect(0, 15, 10, 0));
+    }
+}
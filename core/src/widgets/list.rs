@@ -0,0 +1,123 @@
+//! Scrolling lists.
+
+use arrayvec::{ArrayVec, ArrayString};
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Rectangle, PrimitiveStyle},
+    text::{Text, Baseline}
+};
+
+use super::{Widget, Font};
+
+/// A list of up to `N` items of up to `LEN` bytes each, one per line. The selected item is drawn
+/// inverted, and the list is scrolled to keep it in view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct List<const N: usize, const LEN: usize> {
+    pub items: ArrayVec<ArrayString<LEN>, N>,
+    pub selected: Option<usize>,
+    pub font: Font
+}
+
+impl<const N: usize, const LEN: usize> List<N, LEN> {
+    pub fn new(font: Font) -> Self {
+        Self {
+            items: ArrayVec::new(),
+            selected: None,
+            font
+        }
+    }
+
+    /// Add an item, cutting it off if it's too long. Returns `false` if the list is full.
+    pub fn push(&mut self, item: &str) -> bool {
+        let mut text = ArrayString::new();
+        for c in item.chars() {
+            if text.try_push(c).is_err() {
+                break;
+            }
+        }
+        self.items.try_push(text).is_ok()
+    }
+
+    /// Index of the first item shown, for a list `rows` lines high
+    fn first_shown(&self, rows: usize) -> usize {
+        match self.selected {
+            // Keep the selection on the last line shown once it'd go past it
+            Some(selected) if selected >= rows => selected + 1 - rows,
+            _ => 0
+        }
+    }
+}
+
+impl<const N: usize, const LEN: usize> Widget for List<N, LEN> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let line_height = self.font.height();
+        let rows = (area.size.height / line_height).max(1) as usize;
+        let first = self.first_shown(rows);
+
+        for (row, (i, item)) in self.items.iter().enumerate().skip(first).take(rows).enumerate() {
+            let y = (row as u32 * line_height) as i32;
+            let color = if Some(i) == self.selected {
+                // Cannot error
+                let _ = Rectangle::new(Point::new(0, y), Size::new(area.size.width, line_height))
+                    .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                    .draw(target);
+                BinaryColor::Off
+            }
+            else {
+                BinaryColor::On
+            };
+            let _ = Text::with_baseline(item, Point::new(1, y), self.font.style(color), Baseline::Top).draw(target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+
+    fn list(selected: Option<usize>) -> List<8, 4> {
+        let mut list = List::new(Font::Small);
+        for item in ["a", "b", "c", "d", "e"] {
+            assert!(list.push(item));
+        }
+        list.selected = selected;
+        list
+    }
+
+    #[test]
+    fn scrolls_to_selection() {
+        assert_eq!(list(None).first_shown(3), 0);
+        assert_eq!(list(Some(2)).first_shown(3), 0);
+        // Selection stays on the last line
+        assert_eq!(list(Some(3)).first_shown(3), 1);
+        assert_eq!(list(Some(4)).first_shown(3), 2);
+    }
+
+    #[test]
+    fn full() {
+        let mut list = List::<2, 4>::new(Font::Small);
+        assert!(list.push("a"));
+        assert!(list.push("bcdef"));
+        assert!(!list.push("c"));
+        assert_eq!(list.items[1].as_str(), "bcde");
+    }
+
+    #[test]
+    fn selection_inverted() {
+        // Two 6 px lines, scrolled so the selection is on the second
+        let mut display = MockDisplay::new();
+        // The text is drawn over the selection's background
+        display.set_allow_overdraw(true);
+        list(Some(3)).draw(&mut display.cropped(&Rectangle::new(Point::zero(), Size::new(20, 12))));
+        assert_eq!(display.get_pixel(Point::new(0, 0)), None);
+        for y in 6..12 {
+            assert_eq!(display.get_pixel(Point::new(0, y)), Some(BinaryColor::On));
+            assert_eq!(display.get_pixel(Point::new(19, y)), Some(BinaryColor::On));
+        }
+        // Nothing past the area
+        assert_eq!(display.affected_area().bottom_right(), Some(Point::new(19, 11)));
+    }
+}
@@ -0,0 +1,167 @@
+//! A small widget toolkit for building the UI modes out of.
+//!
+//! Widgets are plain values describing what to show. They're drawn into an area of the screen,
+//! using coordinates relative to its top left corner, so they don't need to know where they are.
+//! [`Layout`] splits the screen up into those areas.
+//!
+//! To avoid redrawing what hasn't changed, widgets are kept in a [`Dirty`], which only redraws when
+//! the widget is replaced with a different one, or moved.
+
+use embedded_graphics::{
+    self as gfx,
+    prelude::*,
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    primitives::Rectangle
+};
+
+mod layout;
+mod label;
+mod big_number;
+mod progress;
+mod icon;
+mod list;
+mod status_bar;
+mod arrow;
+pub mod seven_segment;
+
+pub use layout::{Layout, Constraint};
+pub use label::Label;
+pub use big_number::BigNumber;
+pub use progress::ProgressBar;
+pub use icon::Icon;
+pub use list::List;
+pub use status_bar::{StatusBar, StatusItem};
+pub use arrow::Arrow;
+
+/// Something that can be drawn into an area of the screen
+pub trait Widget {
+    /// Draw the widget. `target`'s bounding box is the area it's been given, with the top left at
+    /// the origin. The area has already been cleared to [`BinaryColor::Off`].
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D);
+}
+
+/// Either the widget, or nothing at all
+impl<W: Widget> Widget for Option<W> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        if let Some(widget) = self {
+            widget.draw(target);
+        }
+    }
+}
+
+/// Fonts widgets can use. Fonts aren't `Sync`, so widgets store one of these rather than the font
+/// itself, otherwise they couldn't be sent between RTIC tasks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Font {
+    /// 4x6, for dense text
+    Small,
+    /// 6x10
+    Medium,
+    /// 10x20
+    Large
+}
+
+impl Font {
+    pub fn font(self) -> &'static MonoFont<'static> {
+        match self {
+            Self::Small => &gfx::mono_font::iso_8859_1::FONT_4X6,
+            Self::Medium => &gfx::mono_font::iso_8859_1::FONT_6X10,
+            Self::Large => &gfx::mono_font::iso_8859_1::FONT_10X20
+        }
+    }
+
+    pub fn style(self, color: BinaryColor) -> MonoTextStyle<'static, BinaryColor> {
+        MonoTextStyle::new(self.font(), color)
+    }
+
+    /// Height of a line of text
+    pub fn height(self) -> u32 {
+        self.font().character_size.height
+    }
+}
+
+/// A widget along with where it was last drawn, redrawing it only when it changes
+#[derive(Debug)]
+pub struct Dirty<W> {
+    widget: W,
+    /// Where the widget was last drawn, or `None` if it needs to be drawn
+    drawn: Option<Rectangle>
+}
+
+impl<W: Widget + PartialEq> Dirty<W> {
+    pub fn new(widget: W) -> Self {
+        Self {
+            widget,
+            drawn: None
+        }
+    }
+
+    pub fn get(&self) -> &W {
+        &self.widget
+    }
+
+    /// Replace the widget, marking it to be redrawn if it's different
+    pub fn set(&mut self, widget: W) {
+        if widget != self.widget {
+            self.widget = widget;
+            self.drawn = None;
+        }
+    }
+
+    /// Change the widget in place, marking it to be redrawn if that changed anything
+    pub fn update<F: FnOnce(&mut W)>(&mut self, f: F) where W: Clone {
+        let mut widget = self.widget.clone();
+        f(&mut widget);
+        self.set(widget);
+    }
+
+    /// Mark the widget to be redrawn, e.g. because something else drew over it
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    /// Draw the widget into `area` if it's changed or moved since it was last drawn. Returns
+    /// whether it was drawn.
+    pub fn draw<D: DrawTarget<Color = BinaryColor>>(&mut self, target: &mut D, area: Rectangle) -> bool {
+        if self.drawn == Some(area) {
+            return false;
+        }
+        let mut cropped = target.cropped(&area);
+        // Cannot error
+        let _ = cropped.clear(BinaryColor::Off);
+        self.widget.draw(&mut cropped);
+        self.drawn = Some(area);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+
+    #[test]
+    fn dirty_redraws_on_change() {
+        let mut display = MockDisplay::<BinaryColor>::new();
+        display.set_allow_overdraw(true);
+        let area = Rectangle::new(Point::new(0, 0), Size::new(20, 10));
+
+        let mut bar = Dirty::new(ProgressBar::new(1, 2));
+        assert!(bar.draw(&mut display, area));
+        assert!(!bar.draw(&mut display, area));
+
+        // Same value doesn't redraw
+        bar.set(ProgressBar::new(1, 2));
+        assert!(!bar.draw(&mut display, area));
+
+        bar.update(|b| b.value = 2);
+        assert!(bar.draw(&mut display, area));
+
+        // Moving redraws
+        assert!(bar.draw(&mut display, area.offset(-1)));
+
+        bar.invalidate();
+        assert!(bar.draw(&mut display, area.offset(-1)));
+    }
+}
@@ -0,0 +1,73 @@
+//! Progress bars.
+
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Rectangle, PrimitiveStyle}
+};
+
+use super::Widget;
+
+/// An outlined bar, filled in proportion to `value / max`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ProgressBar {
+    pub value: u32,
+    pub max: u32
+}
+
+impl ProgressBar {
+    pub fn new(value: u32, max: u32) -> Self {
+        Self {
+            value,
+            max
+        }
+    }
+}
+
+impl Widget for ProgressBar {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        // Cannot error
+        let _ = area.into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1)).draw(target);
+
+        // Inside the outline, leaving a 1 px gap
+        let inner = area.offset(-2);
+        let filled = if self.max == 0 {
+            0
+        }
+        else {
+            (inner.size.width as u64 * self.value.min(self.max) as u64 / self.max as u64) as u32
+        };
+        let _ = Rectangle::new(inner.top_left, Size::new(filled, inner.size.height))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+
+    fn draw(bar: ProgressBar) -> MockDisplay<BinaryColor> {
+        let mut display = MockDisplay::new();
+        bar.draw(&mut display.cropped(&Rectangle::new(Point::zero(), Size::new(12, 5))));
+        display
+    }
+
+    /// Pattern for a 12x5 bar with the middle row as given
+    fn pattern(fill: &str) -> [&str; 5] {
+        ["############", "#          #", fill, "#          #", "############"]
+    }
+
+    #[test]
+    fn fill() {
+        draw(ProgressBar::new(0, 10)).assert_pattern(&pattern("#          #"));
+        draw(ProgressBar::new(5, 10)).assert_pattern(&pattern("# ####     #"));
+        draw(ProgressBar::new(10, 10)).assert_pattern(&pattern("# ######## #"));
+        // Past the end stays full
+        draw(ProgressBar::new(25, 10)).assert_pattern(&pattern("# ######## #"));
+        // Nothing to be a fraction of
+        draw(ProgressBar::new(5, 0)).assert_pattern(&pattern("#          #"));
+    }
+}
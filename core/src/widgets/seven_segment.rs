@@ -0,0 +1,179 @@
+//! Seven-segment digits, drawn with filled rectangles so they're fast to render at any size.
+
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Rectangle, PrimitiveStyle}
+};
+
+/// Segments lit for each digit: bit 0 = a (top) through bit 6 = g (middle), clockwise from the top
+const SEGMENTS: [u8; 10] = [
+    0b0111111, // 0
+    0b0000110, // 1
+    0b1011011, // 2
+    0b1001111, // 3
+    0b1100110, // 4
+    0b1101101, // 5
+    0b1111101, // 6
+    0b0000111, // 7
+    0b1111111, // 8
+    0b1101111  // 9
+];
+
+/// Seven-segment digits of a given size
+#[derive(Debug, Copy, Clone)]
+pub struct SevenSegment {
+    /// Size of a digit
+    pub size: Size,
+    /// Thickness of a segment
+    pub thickness: u32
+}
+
+impl SevenSegment {
+    /// Digits of the given width, with the height and thickness in proportion
+    pub fn with_width(width: u32) -> Self {
+        // Segments need some room between them to look like a digit at all
+        let width = width.max(3);
+        Self {
+            size: Size::new(width, width * 15 / 8),
+            thickness: (width / 5).max(1)
+        }
+    }
+
+    /// Shrink the digits if needed so they're no taller than `height`
+    pub fn fit_height(self, height: u32) -> Self {
+        if self.size.height <= height {
+            self
+        }
+        else {
+            Self::with_width(height * 8 / 15)
+        }
+    }
+
+    /// Width of the colon between digits
+    pub fn colon_width(&self) -> u32 {
+        self.thickness
+    }
+
+    /// Draw a digit (0-9) with its top left corner at `top_left`
+    pub fn draw_digit<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D, top_left: Point, digit: u8) {
+        let Some(&segments) = SEGMENTS.get(digit as usize) else {
+            return;
+        };
+        let (w, h, t) = (self.size.width as i32, self.size.height as i32, self.thickness as i32);
+        // Top of the middle segment
+        let mid = (h - t) / 2;
+
+        // (x, y, width, height) for segments a-g
+        let rects = [
+            (t, 0, w - 2 * t, t),
+            (w - t, t, t, mid - t),
+            (w - t, mid + t, t, h - mid - 2 * t),
+            (t, h - t, w - 2 * t, t),
+            (0, mid + t, t, h - mid - 2 * t),
+            (0, t, t, mid - t),
+            (t, mid, w - 2 * t, t)
+        ];
+
+        let style = PrimitiveStyle::with_fill(BinaryColor::On);
+        for (i, &(x, y, rw, rh)) in rects.iter().enumerate() {
+            if segments & (1 << i) != 0 {
+                // Cannot error
+                let _ = Rectangle::new(top_left + Point::new(x, y), Size::new(rw as u32, rh as u32))
+                    .into_styled(style)
+                    .draw(target);
+            }
+        }
+    }
+
+    /// Draw a two-digit number, returning the x coordinate just past it
+    pub fn draw_number<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D, top_left: Point, n: u32, gap: u32) -> i32 {
+        self.draw_digit(target, top_left, (n / 10 % 10) as u8);
+        let second = top_left + Point::new((self.size.width + gap) as i32, 0);
+        self.draw_digit(target, second, (n % 10) as u8);
+        second.x + self.size.width as i32
+    }
+
+    /// Draw a colon with its top left corner at `top_left`
+    pub fn draw_colon<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D, top_left: Point) {
+        let h = self.size.height as i32;
+        let dot = Size::new(self.thickness, self.thickness);
+        let style = PrimitiveStyle::with_fill(BinaryColor::On);
+        for y in [h / 3, h * 2 / 3] {
+            let _ = Rectangle::new(top_left + Point::new(0, y - self.thickness as i32 / 2), dot)
+                .into_styled(style)
+                .draw(target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+
+    fn draw(digit: u8) -> MockDisplay<BinaryColor> {
+        let mut display = MockDisplay::new();
+        SevenSegment::with_width(5).draw_digit(&mut display, Point::zero(), digit);
+        display
+    }
+
+    #[test]
+    fn digit_masks() {
+        draw(8).assert_pattern(&[
+            " ### ",
+            "#   #",
+            "#   #",
+            "#   #",
+            " ### ",
+            "#   #",
+            "#   #",
+            "#   #",
+            " ### "
+        ]);
+        draw(1).assert_pattern(&[
+            "     ",
+            "    #",
+            "    #",
+            "    #",
+            "     ",
+            "    #",
+            "    #",
+            "    #"
+        ]);
+        draw(2).assert_pattern(&[
+            " ### ",
+            "    #",
+            "    #",
+            "    #",
+            " ### ",
+            "#    ",
+            "#    ",
+            "#    ",
+            " ### "
+        ]);
+        draw(7).assert_pattern(&[
+            " ### ",
+            "    #",
+            "    #",
+            "    #",
+            "     ",
+            "    #",
+            "    #",
+            "    #"
+        ]);
+    }
+
+    #[test]
+    fn not_a_digit_draws_nothing() {
+        assert_eq!(draw(10), MockDisplay::new());
+    }
+
+    #[test]
+    fn fit_height() {
+        let digits = SevenSegment::with_width(16);
+        assert_eq!(digits.size, Size::new(16, 30));
+        assert_eq!(digits.fit_height(30).size, digits.size);
+        assert_eq!(digits.fit_height(15).size, Size::new(8, 15));
+    }
+}
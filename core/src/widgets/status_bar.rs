@@ -0,0 +1,82 @@
+//! A bar along the top of the screen.
+
+use arrayvec::{ArrayVec, ArrayString};
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Line, Rectangle, PrimitiveStyle},
+    text::{Text, Baseline}
+};
+
+use super::{Widget, Font, Icon};
+
+/// Most items a status bar can show
+pub const MAX_ITEMS: usize = 5;
+/// Space between items
+const ITEM_GAP: i32 = 3;
+
+/// An icon on the right of a status bar, with optional text to its left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusItem {
+    pub icon: Icon,
+    pub label: ArrayString<4>
+}
+
+impl StatusItem {
+    pub fn new(icon: Icon) -> Self {
+        Self {
+            icon,
+            label: ArrayString::new()
+        }
+    }
+}
+
+/// Text on the left, icons on the right, with a line underneath
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StatusBar {
+    pub text: ArrayString<16>,
+    /// Shown from right to left
+    pub items: ArrayVec<StatusItem, MAX_ITEMS>
+}
+
+impl StatusBar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Height of the bar, including the line
+    pub const HEIGHT: u32 = 10;
+}
+
+impl Widget for StatusBar {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let bottom = area.size.height as i32 - 1;
+        let style = Font::Small.style(BinaryColor::On);
+        let char_width = Font::Small.font().character_size.width as i32;
+
+        // Cannot error
+        let _ = Text::with_baseline(&self.text, Point::new(1, 2), style, Baseline::Top).draw(target);
+
+        let mut right = area.size.width as i32 - 1;
+        for item in &self.items {
+            let left = right - item.icon.size.width as i32;
+            let mut icon_area = target.cropped(&Rectangle::new(
+                Point::new(left, 0),
+                Size::new(item.icon.size.width, bottom as u32)
+            ));
+            item.icon.draw(&mut icon_area);
+            right = left;
+
+            if !item.label.is_empty() {
+                right -= 1 + char_width * item.label.len() as i32;
+                let _ = Text::with_baseline(&item.label, Point::new(right, 2), style, Baseline::Top).draw(target);
+            }
+            right -= ITEM_GAP;
+        }
+
+        let _ = Line::new(Point::new(0, bottom), Point::new(area.size.width as i32 - 1, bottom))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(target);
+    }
+}
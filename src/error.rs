@@ -1,6 +1,102 @@
-//! Error types
+//! Error types, and the central error handler.
+//!
+//! Tasks pass errors they can't deal with to [`report()`], which logs them and keeps the latest one
+//! for the UI to show in a banner, instead of panicking.
+
+use core::cell::RefCell;
+use core::fmt;
+use cortex_m::interrupt::{self, Mutex};
+use stm32l0xx_hal as hal;
+
+use crate::nmea::NmeaError;
+use crate::state::settings::SettingsError;
 
 /// The error type returned by main()
 #[derive(Debug)]
 pub enum MainError {
-}
\ No newline at end of file
+    /// SPI error talking to the display
+    Display(hal::spi::Error),
+    /// UART error talking to the GPS
+    GpsUart(hal::serial::Error),
+    /// Invalid NMEA sentence from the GPS
+    Nmea(NmeaError),
+    /// Error writing to EEPROM
+    Storage(hal::flash::Error),
+    /// Saved settings couldn't be loaded
+    Settings(SettingsError),
+    /// Error setting the RTC
+    Rtc(hal::rtc::Error)
+}
+
+impl fmt::Display for MainError {
+    /// Short description, to fit in the error banner
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Display(e) => write!(f, "display: {:?}", e),
+            Self::GpsUart(e) => write!(f, "GPS UART: {:?}", e),
+            Self::Nmea(e) => write!(f, "NMEA: {:?}", e),
+            Self::Storage(e) => write!(f, "EEPROM: {:?}", e),
+            Self::Settings(e) => write!(f, "settings: {:?}", e),
+            Self::Rtc(e) => write!(f, "RTC: {:?}", e)
+        }
+    }
+}
+
+impl From<hal::spi::Error> for MainError {
+    fn from(e: hal::spi::Error) -> Self {
+        Self::Display(e)
+    }
+}
+
+impl From<hal::serial::Error> for MainError {
+    fn from(e: hal::serial::Error) -> Self {
+        Self::GpsUart(e)
+    }
+}
+
+impl From<NmeaError> for MainError {
+    fn from(e: NmeaError) -> Self {
+        Self::Nmea(e)
+    }
+}
+
+impl From<hal::flash::Error> for MainError {
+    fn from(e: hal::flash::Error) -> Self {
+        Self::Storage(e)
+    }
+}
+
+impl From<SettingsError> for MainError {
+    fn from(e: SettingsError) -> Self {
+        Self::Settings(e)
+    }
+}
+
+impl From<hal::rtc::Error> for MainError {
+    fn from(e: hal::rtc::Error) -> Self {
+        Self::Rtc(e)
+    }
+}
+
+/// The latest error that hasn't been shown yet
+static LATEST: Mutex<RefCell<Option<MainError>>> = Mutex::new(RefCell::new(None));
+
+/// Central error handler: log the error, and keep it to show to the user
+pub fn report(e: MainError) {
+    log::error!("{}", e);
+    interrupt::free(|cs| {
+        *LATEST.borrow(cs).borrow_mut() = Some(e);
+    });
+}
+
+/// Report the error if there was one
+pub fn handle(res: Result<(), MainError>) {
+    if let Err(e) = res {
+        report(e);
+    }
+}
+
+/// Take the latest error reported since the last call, to show it
+pub fn take_latest() -> Option<MainError> {
+    interrupt::free(|cs| LATEST.borrow(cs).borrow_mut().take())
+}
@@ -0,0 +1,201 @@
+//! Logging utils
+//!
+//! [`Logger`] formats records and passes them to a [`Backend`]. The backend is picked at compile
+//! time by feature: one output out of `log-semihosting` (default), `log-rtt` and `log-uart`, plus
+//! optionally `log-ring` to also keep recent lines in RAM to show on the watch itself.
+//!
+//! Trace logging can be compiled out entirely with the `strip-trace` feature. Otherwise the level
+//! can be changed at runtime with [`Logger::set_level()`].
+//!
+//! Lines are prefixed with the time since boot, in seconds, once a timestamp source has been set
+//! with [`set_timestamp_source()`].
+
+use core::{
+    fmt::Write,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering}
+};
+use log::{Log, Record, LevelFilter, Metadata};
+use gps_watch_core::settings::level_filter_from_u8;
+
+#[cfg(feature = "log-semihosting")]
+pub mod semihosting;
+#[cfg(feature = "log-rtt")]
+pub mod rtt;
+#[cfg(feature = "log-uart")]
+pub mod uart;
+#[cfg(feature = "log-ring")]
+pub mod ring;
+
+#[cfg(any(
+    all(feature = "log-semihosting", feature = "log-rtt"),
+    all(feature = "log-semihosting", feature = "log-uart"),
+    all(feature = "log-rtt", feature = "log-uart")
+))]
+compile_error!("only one of the `log-semihosting`, `log-rtt` and `log-uart` features can be enabled");
+
+/// Where log output goes, other than the ring buffer
+#[cfg(feature = "log-semihosting")]
+type Output = semihosting::Semihosting;
+#[cfg(feature = "log-rtt")]
+type Output = rtt::Rtt;
+#[cfg(feature = "log-uart")]
+type Output = uart::Uart;
+#[cfg(not(any(feature = "log-semihosting", feature = "log-rtt", feature = "log-uart")))]
+type Output = ();
+
+/// All enabled backends
+#[cfg(feature = "log-ring")]
+pub type Backends = (Output, ring::RingBuffer);
+#[cfg(not(feature = "log-ring"))]
+pub type Backends = Output;
+
+/// Somewhere to write log output to.
+///
+/// Records are written in several calls, and records from different priorities can interleave, so
+/// backends only need to keep each call intact.
+pub trait Backend: Send + Sync {
+    fn write_str(&self, s: &str);
+}
+
+/// No output
+impl Backend for () {
+    fn write_str(&self, _s: &str) { }
+}
+
+/// Output to two backends
+impl<A: Backend, B: Backend> Backend for (A, B) {
+    fn write_str(&self, s: &str) {
+        self.0.write_str(s);
+        self.1.write_str(s);
+    }
+}
+
+/// Function that returns the current time in milliseconds, stored as a pointer since there's no
+/// atomic `Option<fn()>`. Null until set.
+static TIMESTAMP_SOURCE: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Set the function used to timestamp log lines, which returns the time in milliseconds
+pub fn set_timestamp_source(source: fn() -> u64) {
+    TIMESTAMP_SOURCE.store(source as *mut (), Ordering::Relaxed);
+}
+
+fn timestamp_ms() -> Option<u64> {
+    let source = TIMESTAMP_SOURCE.load(Ordering::Relaxed);
+    if source.is_null() {
+        None
+    }
+    else {
+        // Safe because only `fn() -> u64` pointers are stored
+        let source = unsafe { core::mem::transmute::<*mut (), fn() -> u64>(source) };
+        Some(source())
+    }
+}
+
+/// Adapter to use a [`Backend`] with `write!()`
+struct BackendWriter<'a, B>(&'a B);
+impl<'a, B: Backend> Write for BackendWriter<'a, B> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0.write_str(s);
+        Ok(())
+    }
+}
+
+/// An implementation of [`log::Log`] that writes to a [`Backend`]
+pub struct Logger<B> {
+    backend: B,
+    /// Level for targets that aren't in `filters`, as a `LevelFilter` cast to `u8`
+    level: AtomicU8,
+    /// Levels for targets starting with the given prefix. The first match is used.
+    filters: &'static [(&'static str, LevelFilter)]
+}
+
+impl<B: Backend> Log for Logger<B> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let level = self.filters.iter()
+            .find(|(prefix, _)| metadata.target().starts_with(prefix))
+            .map_or_else(|| self.level(), |&(_, level)| level);
+        metadata.level() <= level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let mut w = BackendWriter(&self.backend);
+
+            // Write timestamp and line header, fail silently
+            let _ = match timestamp_ms() {
+                Some(ms) => write!(w, "{:>5}.{:03} ", ms / 1000, ms % 1000),
+                None => write!(w, "    -.--- ")
+            };
+            let _ = write!(
+                w,
+                "[{level} {target}] {file}:{line}: ",
+                level=record.level(),
+                target=record.target(),
+                file=record.file().unwrap_or("<unknown>"),
+                line=record.line().unwrap_or(0)
+            );
+
+            // Write the formatted output, fail silently
+            let _ = w.write_fmt(*record.args());
+            let _ = w.write_str("\n");
+        }
+    }
+
+    fn flush(&self) { } // Do nothing: backends are either unbuffered or read by something else
+}
+
+impl<B> Logger<B> {
+    pub const fn new(backend: B, level: LevelFilter, filters: &'static [(&'static str, LevelFilter)]) -> Self {
+        Self {
+            backend,
+            level: AtomicU8::new(level as usize as u8),
+            filters
+        }
+    }
+
+    /// The level for targets without their own filter
+    pub fn level(&self) -> LevelFilter {
+        // Only valid levels are stored
+        level_filter_from_u8(self.level.load(Ordering::Relaxed)).unwrap_or(LevelFilter::Trace)
+    }
+
+    /// Change the level for targets without their own filter
+    pub fn set_level(&self, level: LevelFilter) {
+        self.level.store(level as usize as u8, Ordering::Relaxed);
+        // The `log` macros check the global max level before calling the logger, so raise it to
+        // whatever the most verbose filter is
+        let max = self.filters.iter().map(|&(_, l)| l).fold(level, core::cmp::max);
+        // `set_max_level()` needs atomic compare-and-swap, which thumbv6 doesn't have. Only called
+        // from init and the update task, so calls can't race.
+        unsafe { log::set_max_level_racy(max) };
+    }
+}
+
+impl Logger<Backends> {
+    /// Create a logger using the backends selected by feature
+    #[allow(clippy::let_unit_value)] // when there's no output backend
+    pub const fn with_backends(level: LevelFilter, filters: &'static [(&'static str, LevelFilter)]) -> Self {
+        #[cfg(feature = "log-semihosting")]
+        let output = semihosting::Semihosting::new();
+        #[cfg(feature = "log-rtt")]
+        let output = rtt::Rtt::new();
+        #[cfg(feature = "log-uart")]
+        let output = uart::Uart::new();
+        #[cfg(not(any(feature = "log-semihosting", feature = "log-rtt", feature = "log-uart")))]
+        let output = ();
+
+        #[cfg(feature = "log-ring")]
+        let backends = (output, ring::RingBuffer::new());
+        #[cfg(not(feature = "log-ring"))]
+        let backends = output;
+
+        Self::new(backends, level, filters)
+    }
+
+    /// The ring buffer backend, to show recent lines on the watch
+    #[cfg(feature = "log-ring")]
+    pub fn ring(&self) -> &ring::RingBuffer {
+        &self.backend.1
+    }
+}
@@ -0,0 +1,92 @@
+//! Log backend that keeps the most recent output in RAM, to show on the watch itself.
+
+use core::cell::RefCell;
+use cortex_m::interrupt::{self, Mutex};
+
+use super::Backend;
+
+/// Bytes of log output kept
+const SIZE: usize = 1024;
+
+struct Ring {
+    buf: [u8; SIZE],
+    /// Index the next byte goes at
+    head: usize,
+    /// Whether the buffer has wrapped, so all of it is valid
+    full: bool
+}
+
+pub struct RingBuffer {
+    ring: Mutex<RefCell<Ring>>
+}
+
+impl RingBuffer {
+    pub const fn new() -> Self {
+        Self {
+            ring: Mutex::new(RefCell::new(Ring {
+                buf: [0; SIZE],
+                head: 0,
+                full: false
+            }))
+        }
+    }
+
+    /// Copy out the last `N` lines, oldest first. Lines longer than `LEN` are truncated. Returns
+    /// the number of lines found.
+    pub fn last_lines<const N: usize, const LEN: usize>(&self, lines: &mut [arrayvec::ArrayString<LEN>; N]) -> usize {
+        interrupt::free(|cs| {
+            let ring = self.ring.borrow(cs).borrow();
+            let len = if ring.full { SIZE } else { ring.head };
+            // Bytes from newest to oldest
+            let byte_at = |i: usize| ring.buf[(ring.head + SIZE - 1 - i) % SIZE];
+
+            // Find the starts of the last N complete lines by walking back over newlines. The
+            // newest byte is the end of the last line, so skip it.
+            let mut starts = arrayvec::ArrayVec::<usize, N>::new();
+            let mut i = 1;
+            while i < len && !starts.is_full() {
+                if byte_at(i) == b'\n' {
+                    starts.push(i);
+                }
+                i += 1;
+            }
+            // The oldest line might have been partly overwritten, so only include it if the
+            // buffer hasn't wrapped
+            if !ring.full && i == len && !starts.is_full() && len > 0 {
+                starts.push(len);
+            }
+
+            // Copy lines out, oldest first
+            for (line, &start) in lines.iter_mut().zip(starts.iter().rev()) {
+                line.clear();
+                for j in (0..start).rev() {
+                    let b = byte_at(j);
+                    if b == b'\n' {
+                        break;
+                    }
+                    // Only ASCII is kept, so a truncated line can't split a character
+                    if line.try_push(if b.is_ascii() { b as char } else { '?' }).is_err() {
+                        break;
+                    }
+                }
+            }
+            starts.len()
+        })
+    }
+}
+
+impl Backend for RingBuffer {
+    fn write_str(&self, s: &str) {
+        interrupt::free(|cs| {
+            let mut ring = self.ring.borrow(cs).borrow_mut();
+            for &b in s.as_bytes() {
+                let head = ring.head;
+                ring.buf[head] = b;
+                ring.head = (head + 1) % SIZE;
+                if ring.head == 0 {
+                    ring.full = true;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,104 @@
+//! Log backend using SEGGER RTT: output goes into a ring buffer in RAM, which the debug probe reads
+//! in the background. Unlike semihosting, this doesn't halt the core, and doesn't hang without a
+//! debugger attached (output is just dropped once the buffer fills up).
+//!
+//! Only a minimal single up-channel is implemented, in non-blocking trim mode. See
+//! [the RTT documentation](https://wiki.segger.com/RTT) for the layout.
+
+use core::{
+    ffi::c_char,
+    ptr::{self, addr_of_mut},
+    sync::atomic::{compiler_fence, Ordering}
+};
+
+use super::Backend;
+
+/// Size of the up buffer
+const BUFFER_SIZE: usize = 1024;
+/// What the probe looks for to find the control block
+const ID: &[u8; 16] = b"SEGGER RTT\0\0\0\0\0\0";
+
+#[repr(C)]
+struct Channel {
+    name: *const c_char,
+    buffer: *mut u8,
+    size: u32,
+    /// Written by the target
+    write: u32,
+    /// Written by the probe
+    read: u32,
+    flags: u32
+}
+
+#[repr(C)]
+struct ControlBlock {
+    id: [u8; 16],
+    max_up_buffers: u32,
+    max_down_buffers: u32,
+    up: Channel,
+    down: Channel
+}
+
+const EMPTY_CHANNEL: Channel = Channel {
+    name: ptr::null(),
+    buffer: ptr::null_mut(),
+    size: 0,
+    write: 0,
+    read: 0,
+    flags: 0
+};
+
+static mut CONTROL_BLOCK: ControlBlock = ControlBlock {
+    id: [0; 16],
+    max_up_buffers: 1,
+    max_down_buffers: 1,
+    up: EMPTY_CHANNEL,
+    down: EMPTY_CHANNEL
+};
+static mut BUFFER: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+
+pub struct Rtt;
+
+impl Rtt {
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Backend for Rtt {
+    fn write_str(&self, s: &str) {
+        cortex_m::interrupt::free(|_| {
+            // Safe because this is the only place the statics are accessed from, and interrupts are
+            // disabled. The probe only reads the buffer and writes `read`.
+            let cb = unsafe { &mut *addr_of_mut!(CONTROL_BLOCK) };
+
+            // Set up on first use. The ID goes in last, so the probe doesn't find a half-set-up
+            // control block.
+            if cb.id[0] == 0 {
+                cb.up = Channel {
+                    name: c"Terminal".as_ptr(),
+                    buffer: addr_of_mut!(BUFFER) as *mut u8,
+                    size: BUFFER_SIZE as u32,
+                    ..EMPTY_CHANNEL
+                };
+                compiler_fence(Ordering::SeqCst);
+                cb.id = *ID;
+            }
+
+            let read = unsafe { ptr::read_volatile(&cb.up.read) } as usize;
+            let mut write = cb.up.write as usize;
+            for &b in s.as_bytes() {
+                let next = (write + 1) % BUFFER_SIZE;
+                if next == read {
+                    // Full, drop the rest
+                    break;
+                }
+                unsafe { cb.up.buffer.add(write).write_volatile(b) };
+                write = next;
+            }
+            // Make sure the data is in before the probe sees the new write index
+            compiler_fence(Ordering::SeqCst);
+            unsafe { ptr::write_volatile(&mut cb.up.write, write as u32) };
+        });
+    }
+}
@@ -0,0 +1,25 @@
+//! Log backend for printing to the host PC through the debugger.
+//!
+//! Semihosting halts the core for every write, and hangs without a debugger attached, so this is
+//! only for development.
+
+use cortex_m_semihosting::hio::hstdout;
+
+use super::Backend;
+
+pub struct Semihosting;
+
+impl Semihosting {
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Backend for Semihosting {
+    fn write_str(&self, s: &str) {
+        // Fail silently if unable to get hstdout
+        if let Ok(mut fd) = hstdout() {
+            let _ = fd.write_all(s.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Log backend that writes to a UART, for logging without a debug probe.
+//!
+//! The UART is only created in `init`, after logging has started, so it's attached with
+//! [`attach()`]. Output before that is dropped. Writes are blocking, with interrupts disabled, so
+//! logging a lot slows everything down.
+
+use core::cell::RefCell;
+use cortex_m::interrupt::{self, Mutex};
+use embedded_hal::serial::Write;
+use stm32l0xx_hal::serial::{Tx, USART1};
+
+use super::Backend;
+
+static TX: Mutex<RefCell<Option<Tx<USART1>>>> = Mutex::new(RefCell::new(None));
+
+/// Start logging to this UART
+pub fn attach(tx: Tx<USART1>) {
+    interrupt::free(|cs| {
+        *TX.borrow(cs).borrow_mut() = Some(tx);
+    });
+}
+
+pub struct Uart;
+
+impl Uart {
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Backend for Uart {
+    fn write_str(&self, s: &str) {
+        interrupt::free(|cs| {
+            if let Some(tx) = TX.borrow(cs).borrow_mut().as_mut() {
+                for &b in s.as_bytes() {
+                    // Fail silently
+                    let _ = nb::block!(tx.write(b));
+                }
+            }
+        });
+    }
+}
@@ -6,68 +6,85 @@
 #![no_main] // bootup is handled by cortex-m-rt and rtic
 
 
-// Load panic handler
+// Load panic handler, selected by feature
+#[cfg(feature = "panic-semihosting")]
 use panic_semihosting as _;
+// The `panic-reboot` handler is in `peripherals::crash`
+#[cfg(all(feature = "panic-semihosting", feature = "panic-reboot"))]
+compile_error!("only one of the `panic-semihosting` and `panic-reboot` features can be enabled");
+#[cfg(not(any(feature = "panic-semihosting", feature = "panic-reboot")))]
+compile_error!("one of the `panic-semihosting` and `panic-reboot` features must be enabled");
 
 mod logging;
 mod error;
 mod state;
 mod peripherals;
-mod nmea;
+mod util;
 
-use crate::logging::SemihostingLogger;
+// Hardware-free parts, in their own crate so they can be tested on the host
+use gps_watch_core::{activity, geo, nmea, track, trig, widgets};
 
-/// Global logger
-static LOGGER: SemihostingLogger = SemihostingLogger::new(log::Level::Info);
+use log::LevelFilter;
+
+use crate::logging::{Logger, Backends};
+
+/// Global logger, using the backends selected by feature
+/// The level is changed to the one in the settings once they're loaded.
+static LOGGER: Logger<Backends> = Logger::with_backends(LevelFilter::Info, LOG_FILTERS);
+/// Log levels for targets that should differ from the global level
+const LOG_FILTERS: &[(&str, LevelFilter)] = &[
+    ("embedded_sdmmc", LevelFilter::Warn) // Very chatty at info
+];
 
 // RTIC app: handles concurrency and interrupts
 #[rtic::app(
     device = stm32l0xx_hal::pac, // The device's peripheral access crate
     peripherals = true, // Whether or not RTIC should grab the device's peripherals
-    dispatchers = [LPTIM1] // Interrupts that aren't otherwise used, for triggering software tasks
+    dispatchers = [USART4_USART5] // Interrupts that aren't otherwise used, for triggering software tasks
 )]
 mod app {
     // Imports
     use stm32l0xx_hal::{
         self as hal,
         prelude::*,
-        pac::SPI1,
-        rcc::Rcc,
         pwr::PWR,
         rtc::Rtc,
-        spi::Spi
+        exti::{Exti, ConfigurableLine, DirectLine, TriggerEdge}
     };
     use crate::peripherals as perif;
+    use crate::state::{State, Resources, SharedState, settings::{Settings, SettingsError}};
+    use crate::error;
+
+    /// GPS power mode when the battery is fine
+    const GPS_MODE_NORMAL: perif::GpsPowerMode = perif::GpsPowerMode::Periodic { interval_min: 60 };
+    /// GPS power mode when the battery is low: only sync time occasionally
+    const GPS_MODE_LOW_BATTERY: perif::GpsPowerMode = perif::GpsPowerMode::Periodic { interval_min: 360 };
+    /// How often to update when the battery is low, in seconds. Must be well inside the watchdog
+    /// timeout, since the watchdog is fed from the update task.
+    const LOW_BATTERY_UPDATE_S: u32 = 10;
+    /// How often to measure the battery, in seconds
+    const BATTERY_SAMPLE_INTERVAL_S: u64 = 60;
 
     // Resource types
     #[shared]
     struct Shared {
-        rcc: Rcc,
-        pwr: PWR,
-        rtc: Rtc,
-        // haha yes i love type signatures
-        display: perif::SharpLcd<
-            Spi<
-                SPI1,
-                (
-                    hal::gpio::gpioa::PA5<hal::gpio::Analog>,
-                    hal::gpio::gpioa::PA6<hal::gpio::Analog>,
-                    hal::gpio::gpioa::PA7<hal::gpio::Analog>
-                )
-            >,
-            hal::gpio::gpioa::PA4<hal::gpio::Output<hal::gpio::PushPull>>
-        >,
-        gps: perif::Gps
+        state: State,
+        gps: perif::Gps,
+        gps_power: perif::GpsPower<hal::gpio::gpioa::PA8<hal::gpio::Output<hal::gpio::PushPull>>>,
+        battery: perif::Battery<hal::gpio::gpiob::PB1<hal::gpio::Analog>>,
+        /// Seconds between updates, lengthened when the battery is low
+        update_interval_s: u32
     }
 
     #[local]
     struct Local {
-
+        watchdog: hal::watchdog::IndependedWatchdog,
+        extcomin: hal::gpio::gpiob::PB0<hal::gpio::Output<hal::gpio::PushPull>>
     }
 
     // Monotonics
-    #[monotonic(binds = SysTick, default = true)]
-    type SystickMonotonic = systick_monotonic::Systick<100>; // General timer for event scheduling, 10 ms precision
+    #[monotonic(binds = LPTIM1, default = true)]
+    type LptimMonotonic = perif::monotonic::LptimMonotonic; // General timer for event scheduling, keeps running in Stop mode
 
 
     // Initalization function. Called on bootup after RTIC is initialized, to setup shared resources
@@ -78,6 +95,11 @@ mod app {
         // This is safe because this is run before anything else and it's the only initialization
         // Fail silently
         let _ = unsafe { log::set_logger_racy(&crate::LOGGER) };
+        crate::LOGGER.set_level(crate::LOGGER.level());
+        // Timestamp with the monotonic, which reads zero until init is done
+        crate::logging::set_timestamp_source(|| {
+            monotonics::now().ticks() * 1000 / perif::monotonic::TIMER_HZ as u64
+        });
         log::trace!("logging initialized");
 
         // Peripheral shorthand
@@ -88,6 +110,10 @@ mod app {
         log::trace!("setting up RCC");
         let mut rcc = dp.RCC.freeze(hal::rcc::Config::hsi16());
 
+        // Find out why we reset, before anything else can cause one
+        let reset_reason = perif::reset::ResetReason::read_and_clear(&mut rcc);
+        log::info!("reset reason: {:?}", reset_reason);
+
         // Configure power
         log::trace!("setting up PWR");
         let mut pwr = PWR::new(dp.PWR, &mut rcc);
@@ -96,28 +122,39 @@ mod app {
         // Used by RTC and LPUART
         // Safe to enable multiple times
         log::trace!("enabling LSE");
-        let _lse = rcc.enable_lse(&pwr);
+        let lse = rcc.enable_lse(&pwr);
 
         // Configure RTC
         log::trace!("setting up RTC");
-        let mut rtc = Rtc::new(dp.RTC, &mut rcc, &mut pwr, None).unwrap();
-        log::trace!("enabling wakeup interrupt");
-        // Enable wakeup timer interrupt
-        rtc.enable_interrupts(hal::rtc::Interrupts {
-            timestamp: false,
-            wakeup_timer: true,
-            alarm_a: false,
-            alarm_b: false
-        });
-        // Set 1 second wakeup timer
-        rtc.wakeup_timer().start(32768u32); //TODO: Docs are unclear on RTC frequency
+        // Keeps the current time if the RTC kept running through the reset; whether that time is
+        // trustworthy is tracked in the backup registers (see `State::new()`)
+        // Only errors if the date it starts a fresh RTC at is invalid, and with no date given it
+        // uses a fixed, valid one. Nothing could be recovered anyway: the RTC drives every update.
+        let mut rtc = Rtc::new(dp.RTC, &mut rcc, &pwr, None).expect("RTC start date is invalid");
+        // Start 1 second wakeup timer and its interrupt. This always stays at 1 second, since it
+        // also toggles the display's EXTCOMIN.
+        log::trace!("starting wakeup timer");
+        perif::rtc::start_wakeup(&mut rtc);
 
         // Acquire GPIO for pins
         log::trace!("acquiring GPIO");
         let gpioa = dp.GPIOA.split(&mut rcc);
-        //let gpiob = dp.GPIOB.split(&mut rcc);
+        let gpiob = dp.GPIOB.split(&mut rcc);
         let gpioc = dp.GPIOC.split(&mut rcc);
 
+        // Log UART
+        #[cfg(feature = "log-uart")]
+        {
+            let (tx, _rx) = dp.USART1.usart(
+                gpioa.pa9,
+                gpioa.pa10,
+                hal::serial::Config::default().baudrate(115_200.Bd()),
+                &mut rcc
+            ).expect("log UART config is invalid").split(); // Only errors on a fixed config that's valid
+            crate::logging::uart::attach(tx);
+            log::trace!("log UART attached");
+        }
+
         // Buzzer PWM
         log::trace!("creating buzzer");
         let pwm_timer = hal::pwm::Timer::new(dp.TIM2, 2000.Hz(), &mut rcc);
@@ -142,85 +179,244 @@ mod app {
         let display_cs = gpioa.pa4.into_push_pull_output();
         //let sdcard_cs = gpioa.pa3.into_push_pull_output();
 
+        // Check for a crash report from the last boot
+        let mut flash = dp.FLASH;
+        let crash = perif::crash::take_report(&mut flash);
+        if let Some((report, new)) = &crash {
+            log::error!(
+                "{} crash report: {}:{}: {} (sp {:#010x}, backtrace {:#010x?})",
+                if *new { "new" } else { "old" },
+                report.file, report.line, report.message, report.sp, report.backtrace
+            );
+        }
+
+        // Load settings from EEPROM
+        log::trace!("loading settings");
+        let settings_store = perif::SettingsStore::new(flash, &mut rcc);
+        let settings = match settings_store.settings() {
+            Ok(settings) => settings,
+            Err(SettingsError::Blank) => {
+                log::info!("no saved settings, using defaults");
+                Settings::default()
+            }
+            Err(e) => {
+                crate::error::report(e.into());
+                Settings::default()
+            }
+        };
+        crate::LOGGER.set_level(settings.log_level);
+
         // Create display and clear
+        // EXTMODE is tied high and EXTCOMIN is on PB0, toggled from the RTC wakeup interrupt. The
+        // RTC's own 1 Hz output can only go to PC13 or PB14, and the 32-pin package has neither.
+        let mut extcomin = gpiob.pb0.into_push_pull_output();
+        let _ = extcomin.set_low();
         log::trace!("setting up display");
-        let mut display = perif::SharpLcd::new(spi, display_cs);
-        display.send_clear().unwrap();
+        let mut display = perif::SharpLcd::new(spi, display_cs, perif::VcomMode::External);
+        display.set_rotation(match settings.display.rotation {
+            1 => perif::Rotation::Deg90,
+            2 => perif::Rotation::Deg180,
+            3 => perif::Rotation::Deg270,
+            _ => perif::Rotation::Deg0
+        });
+        display.set_inverted(settings.display.inverted);
+        if let Err(e) = display.send_clear() {
+            crate::error::report(e.into());
+        }
 
         // Create UART for GPS
+        // Clocked from HSI16 so that it keeps receiving in Stop mode
         log::trace!("setting up LPUART");
-        let mut gps_uart = dp.LPUART1.usart(
+        perif::power::clock_lpuart_from_hsi16(&mut rcc);
+        let gps_uart = dp.LPUART1.usart(
             // gpioc.pc4,
             // gpioc.pc5,
             gpioc.pc1,
             gpioc.pc0,
             hal::serial::Config::default().baudrate(4800.Bd()), // NMEA 0183 uses 4800 by default
             &mut rcc
-        ).unwrap();
+        ).expect("GPS UART config is invalid"); // Only errors on a bad config, and this one is fixed
         log::trace!("creating GPS object");
         let gps = perif::Gps::new(gps_uart);
 
-        // Create Systick monotonic object
-        log::trace!("creating systick monotonic");
-        let syst = systick_monotonic::Systick::new(cp.SYST, rcc.clocks.sys_clk().0);
+        // GPS power policy: only wake up for time sync by default
+        log::trace!("creating GPS power policy");
+        let gps_enable = gpioa.pa8.into_push_pull_output();
+        let gps_power = perif::GpsPower::new(gps_enable, GPS_MODE_NORMAL);
+
+        // Battery monitor: battery voltage is halved by a divider on PB1
+        log::trace!("setting up ADC");
+        let adc = hal::adc::Adc::new(dp.ADC, &mut rcc);
+        let battery = perif::Battery::new(adc, gpiob.pb1.into_analog());
+
+        // Route wakeup sources through EXTI so they can wake the MCU from Stop mode
+        log::trace!("setting up EXTI");
+        let mut exti = Exti::new(dp.EXTI);
+        exti.listen_configurable(ConfigurableLine::RtcWakeup, TriggerEdge::Rising);
+        exti.listen_direct(DirectLine::Lpuart1);
+        exti.listen_direct(DirectLine::Lptim1);
+
+        // Enter Stop mode whenever idle
+        log::trace!("configuring Stop mode");
+        let mut scb = cp.SCB;
+        perif::power::configure_stop_mode(&mut scb, &mut rcc, &mut pwr);
+
+        // Create LPTIM monotonic object
+        log::trace!("creating LPTIM monotonic");
+        let mono = perif::monotonic::LptimMonotonic::new(dp.LPTIM, &mut rcc, &lse);
+
+        // Create UI state
+        let state = State::new(
+            Resources { rtc, settings_store, display },
+            SharedState::new(settings, reset_reason, crash)
+        );
+
+        // Take the first battery measurement right away
+        if sample_battery::spawn().is_err() {
+            log::warn!("sample_battery already pending");
+        }
+
+        // Start the watchdog last, so slow initialization can't trip it
+        log::trace!("starting watchdog");
+        let watchdog = perif::reset::start_watchdog(dp.IWDG, &mut rcc);
 
         log::info!("initalization complete");
         (
             Shared {
-                rcc,
-                pwr,
-                rtc,
-                display,
-                gps
+                state,
+                gps,
+                gps_power,
+                battery,
+                update_interval_s: 1
             },
-            Local {},
-            init::Monotonics(syst)
+            Local {
+                watchdog,
+                extcomin
+            },
+            init::Monotonics(mono)
         )
     }
 
-    /// Triggers on RTC
-    #[task(binds = RTC, shared = [rtc])]
-    fn on_rtc(_c: on_rtc::Context) {
+    /// Runs when no tasks are running: sleeps in Stop mode until an interrupt wakes the MCU.
+    /// The LPTIM monotonic keeps counting, and wakes the MCU for scheduled tasks.
+    #[idle]
+    fn idle(_c: idle::Context) -> ! {
+        log::trace!("idle()");
+
+        loop {
+            perif::power::stop();
+        }
+    }
+
+    /// Triggers on RTC, every second
+    #[task(binds = RTC, shared = [state, update_interval_s], local = [extcomin, ticks: u32 = 0])]
+    fn on_rtc(mut c: on_rtc::Context) {
         log::trace!("on_rtc()");
 
-        todo!()
+        // Clear the wakeup timer flag and its EXTI line, otherwise the interrupt keeps firing
+        c.shared.state.lock(|state: &mut State| {
+            let _ = state.resources().rtc.wakeup_timer().wait(); // cannot error
+        });
+        Exti::unpend(ConfigurableLine::RtcWakeup);
+
+        // Toggle the display's VCOM. Done here rather than in `update` so it keeps happening at
+        // 1 Hz when updates are slowed down, or stall.
+        let _ = c.local.extcomin.toggle(); // cannot error
+
+        // Update state every second, or every few seconds when the battery is low
+        *c.local.ticks += 1;
+        if *c.local.ticks < c.shared.update_interval_s.lock(|interval_s| *interval_s) {
+            return;
+        }
+        *c.local.ticks = 0;
+        if update::spawn().is_err() {
+            log::warn!("update already pending");
+        }
+        if update_gps_power::spawn().is_err() {
+            log::warn!("update_gps_power already pending");
+        }
     }
 
     /// Triggers on LPUART
-    #[task(binds = AES_RNG_LPUART1, shared = [gps])] // Weird interrupt name because it's shared by AES and LPUART?
+    #[task(binds = AES_RNG_LPUART1, shared = [gps, gps_power, state])] // Weird interrupt name because it's shared by AES and LPUART?
     fn on_lpuart(mut c: on_lpuart::Context) {
         log::trace!("on_lpuart()");
 
         // Acquire lock on gps resource
-        c.shared.gps.lock(|gps: &mut perif::Gps| {
+        let (sentences, errors) = c.shared.gps.lock(|gps: &mut perif::Gps| {
             // Parse all received bytes into sentences
-            let _sentences = gps.recv();
+            (gps.recv(), gps.errors())
+        });
+
+        let now = monotonics::now();
+        (c.shared.state, c.shared.gps_power).lock(|state: &mut State, gps_power: &mut perif::GpsPower<_>| {
+            state.shared_state().gps.errors = errors;
+            for sentence in &sentences {
+                if state.apply_gps(sentence, now) {
+                    gps_power.fix_acquired(now);
+                }
+            }
+        });
+    }
 
-            todo!();
+    /// Turns the GPS on and off according to its power mode
+    #[task(shared = [gps, gps_power, state])]
+    fn update_gps_power(c: update_gps_power::Context) {
+        log::trace!("update_gps_power()");
+
+        let now = monotonics::now();
+        let res = (c.shared.gps, c.shared.gps_power, c.shared.state).lock(|gps: &mut perif::Gps, gps_power: &mut perif::GpsPower<_>, state: &mut State| {
+            // Recording needs a fix every few seconds
+            gps_power.set_keep_on(state.shared_state().recording);
+            let res = gps_power.update(gps, now);
+            // What it last reported is out of date once it's not running
+            if !gps_power.is_on() {
+                state.shared_state().gps.clear();
+            }
+            res
         });
+        error::handle(res);
     }
 
-    /// Toggles VCOM and flushes the display's buffer every second
-    #[task(shared = [display])]
-    fn flush_display(mut c: flush_display::Context) {
-        log::trace!("flush_display()");
-
-        // Acquire lock on display resource
-        c.shared.display.lock(|disp: &mut perif::SharpLcd<_, _>| {
-            // Toggle VCOM as required by display spec
-            disp.toggle_vcom();
-            // Flush display, print any SPI errors
-            if let Err(e) = disp.flush() {
-                log::error!("error flushing display: {:?}", e);
+    /// Measures the battery, and cuts back power use if it's getting low
+    #[task(shared = [state, gps_power, battery, update_interval_s])]
+    fn sample_battery(c: sample_battery::Context) {
+        log::trace!("sample_battery()");
+
+        (c.shared.state, c.shared.gps_power, c.shared.battery, c.shared.update_interval_s).lock(|state: &mut State, gps_power: &mut perif::GpsPower<_>, battery: &mut perif::Battery<_>, update_interval_s: &mut u32| {
+            let changed = battery.sample();
+            state.shared_state().battery = battery.status();
+
+            if let Some(level) = changed {
+                log::info!("battery level: {:?}", level);
+                let (gps_mode, interval_s) = match level {
+                    perif::BatteryLevel::Ok => (GPS_MODE_NORMAL, 1),
+                    perif::BatteryLevel::Low => (GPS_MODE_LOW_BATTERY, LOW_BATTERY_UPDATE_S),
+                    perif::BatteryLevel::Critical => (perif::GpsPowerMode::Off, LOW_BATTERY_UPDATE_S)
+                };
+                gps_power.set_mode(gps_mode);
+                // Redraw less often to save power
+                *update_interval_s = interval_s;
             }
         });
+
+        if sample_battery::spawn_after(perif::monotonic::Duration::secs(BATTERY_SAMPLE_INTERVAL_S)).is_err() {
+            log::warn!("sample_battery already pending");
+        }
     }
 
-    /// Updates the state
-    #[task]
-    fn update(_c: update::Context) {
+    /// Updates the state and redraws, then feeds the watchdog
+    #[task(shared = [state], local = [watchdog])]
+    fn update(mut c: update::Context) {
         log::trace!("update()");
 
-        todo!()
+        let res = c.shared.state.lock(|state: &mut State| {
+            state.update();
+            state.draw()
+        });
+        error::handle(res);
+
+        // Only fed here, so if the update loop wedges or stops being scheduled the watch resets
+        c.local.watchdog.feed();
     }
 }
\ No newline at end of file
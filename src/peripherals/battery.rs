@@ -0,0 +1,90 @@
+//! Battery monitoring using the ADC.
+//!
+//! Reads the battery sense pin and VREFINT. Turning those into a voltage and level is done in
+//! [`gps_watch_core::battery`].
+
+use embedded_hal::adc::{Channel, OneShot};
+use stm32l0xx_hal::{
+    adc::{Adc, Ready, VRef, SampleTime},
+    calibration::VrefintCal,
+    pac
+};
+use gps_watch_core::battery::{to_millivolts, percent_from_mv, level_from_mv};
+
+pub use gps_watch_core::battery::{BatteryLevel, BatteryStatus};
+
+/// Battery monitor, using the ADC and a battery sense pin
+pub struct Battery<PIN> {
+    adc: Adc<Ready>,
+    vref: VRef,
+    pin: PIN,
+    status: Option<BatteryStatus>
+}
+impl<PIN> core::fmt::Debug for Battery<PIN> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Battery")
+            .field("status", &self.status)
+            .finish_non_exhaustive() // because the ADC doesn't have Debug
+    }
+}
+
+impl<PIN: Channel<Adc<Ready>, ID = u8>> Battery<PIN> {
+    pub fn new(mut adc: Adc<Ready>, pin: PIN) -> Self {
+        // VREFINT needs a long sampling time (at least 10 µs)
+        adc.set_sample_time(SampleTime::T_160_5);
+        let mut vref = VRef::new();
+        vref.enable(&mut adc);
+
+        Self {
+            adc,
+            vref,
+            pin,
+            status: None
+        }
+    }
+
+    /// Measure the battery. Returns the new level if it changed. If the measurement fails, the
+    /// last status is kept.
+    pub fn sample(&mut self) -> Option<BatteryLevel> {
+        // VREFINT is turned off in Stop mode, and waking up doesn't wait for it to come back (see
+        // `power::configure_stop_mode()`), so it may not be ready yet. Reading it early gives a
+        // garbage supply voltage. Takes at most a few milliseconds.
+        // Safe because this only reads a status flag
+        let pwr = unsafe { &*pac::PWR::ptr() };
+        while pwr.csr.read().vrefintrdyf().bit_is_clear() {}
+
+        // A failed reading would look like a flat battery and turn things off, so keep the last
+        // status instead
+        let vrefint: Result<u16, _> = nb::block!(self.adc.read(&mut self.vref));
+        let raw: Result<u16, _> = nb::block!(self.adc.read(&mut self.pin));
+        let millivolts = match (vrefint, raw) {
+            (Ok(vrefint), Ok(raw)) => to_millivolts(VrefintCal::get().read(), vrefint, raw),
+            _ => None
+        };
+        let Some(millivolts) = millivolts else {
+            log::warn!("battery measurement failed, keeping {:?}", self.status);
+            return None;
+        };
+
+        let previous = self.status.map(|s| s.level);
+        let level = level_from_mv(millivolts, previous.unwrap_or(BatteryLevel::Ok));
+        self.status = Some(BatteryStatus {
+            millivolts,
+            percent: percent_from_mv(millivolts),
+            level
+        });
+        log::debug!("battery: {:?}", self.status);
+
+        if previous != Some(level) {
+            Some(level)
+        }
+        else {
+            None
+        }
+    }
+
+    /// The last measurement, or `None` if the battery hasn't been sampled yet
+    pub fn status(&self) -> Option<BatteryStatus> {
+        self.status
+    }
+}
@@ -0,0 +1,222 @@
+//! Crash reports: a panic handler for when there's no debugger attached, and reading back what it
+//! saved.
+//!
+//! With the `panic-reboot` feature, a panic saves the message, location and a snapshot of the
+//! stack to a reserved area of EEPROM, then resets. The reset is a software reset, so the next boot
+//! shows the diagnostics screen, which includes the report.
+//!
+//! The panic handler can't use the HAL's `FLASH`, since it's owned by the settings store and may
+//! be in use, so it programs the EEPROM through the registers directly.
+
+use arrayvec::ArrayString;
+use stm32l0xx_hal::pac;
+
+use crate::peripherals::eeprom::{CRASH_REPORT_ADDRESS, CRASH_REPORT_SIZE};
+
+/// Marks a saved crash report ("CRSH")
+const MAGIC: u32 = 0x4853_5243;
+/// Maximum length of the saved file path, counting from the end
+pub const FILE_LEN: usize = 24;
+/// Maximum length of the saved panic message
+pub const MESSAGE_LEN: usize = 64;
+/// Number of return addresses saved from the stack
+pub const BACKTRACE_LEN: usize = 4;
+
+// Report layout, in words
+const WORD_MAGIC: usize = 0;
+const WORD_SHOWN: usize = 1;
+const WORD_LINE: usize = 2;
+const WORD_SP: usize = 3;
+const WORD_BACKTRACE: usize = 4;
+const WORD_FILE: usize = WORD_BACKTRACE + BACKTRACE_LEN;
+const WORD_MESSAGE: usize = WORD_FILE + FILE_LEN / 4;
+const REPORT_WORDS: usize = WORD_MESSAGE + MESSAGE_LEN / 4;
+const _: () = assert!(REPORT_WORDS * 4 <= CRASH_REPORT_SIZE);
+
+/// What was saved about a panic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    /// End of the path of the source file that panicked
+    pub file: ArrayString<FILE_LEN>,
+    pub line: u32,
+    /// Panic message, truncated
+    pub message: ArrayString<MESSAGE_LEN>,
+    /// Stack pointer in the panic handler
+    pub sp: u32,
+    /// Values on the stack that look like return addresses, innermost first, 0 if not found
+    pub backtrace: [u32; BACKTRACE_LEN]
+}
+
+impl CrashReport {
+    #[cfg(feature = "panic-reboot")]
+    fn to_words(&self) -> [u32; REPORT_WORDS] {
+        let mut words = [0u32; REPORT_WORDS];
+        words[WORD_MAGIC] = MAGIC;
+        words[WORD_LINE] = self.line;
+        words[WORD_SP] = self.sp;
+        words[WORD_BACKTRACE..WORD_FILE].copy_from_slice(&self.backtrace);
+        pack_str(&mut words[WORD_FILE..WORD_MESSAGE], &self.file);
+        pack_str(&mut words[WORD_MESSAGE..], &self.message);
+        words
+    }
+
+    fn from_words(words: &[u32; REPORT_WORDS]) -> Option<Self> {
+        if words[WORD_MAGIC] != MAGIC {
+            return None;
+        }
+        let mut backtrace = [0u32; BACKTRACE_LEN];
+        backtrace.copy_from_slice(&words[WORD_BACKTRACE..WORD_FILE]);
+        Some(Self {
+            file: unpack_str(&words[WORD_FILE..WORD_MESSAGE]),
+            line: words[WORD_LINE],
+            message: unpack_str(&words[WORD_MESSAGE..]),
+            sp: words[WORD_SP],
+            backtrace
+        })
+    }
+}
+
+/// Read the saved crash report, if there is one. Returns it along with whether this is the first
+/// time it's been read, then marks it as read.
+///
+/// Takes the FLASH peripheral, since it's written to, so this must be called before it's handed
+/// to the settings store.
+pub fn take_report(_flash: &mut pac::FLASH) -> Option<(CrashReport, bool)> {
+    let address = CRASH_REPORT_ADDRESS as *mut u32;
+    let mut words = [0u32; REPORT_WORDS];
+    for (i, word) in words.iter_mut().enumerate() {
+        // Safe, as the report area lies within the EEPROM, which is always readable
+        *word = unsafe { address.add(i).read_volatile() };
+    }
+    let report = CrashReport::from_words(&words)?;
+
+    let first = words[WORD_SHOWN] == 0;
+    if first {
+        // Safe because we hold the FLASH peripheral, and the address is in EEPROM
+        unsafe {
+            eeprom_write(&[1], address.add(WORD_SHOWN));
+        }
+    }
+    Some((report, first))
+}
+
+/// Write words to EEPROM through the registers directly, skipping words that are already right
+///
+/// # Safety
+/// Nothing else may be writing to the flash or EEPROM, and `address` must be within the EEPROM
+unsafe fn eeprom_write(words: &[u32], address: *mut u32) {
+    let flash = &*pac::FLASH::ptr();
+
+    // Wait for anything in progress, then unlock the data EEPROM (see section 3.3.4 of the
+    // reference manual)
+    while flash.sr.read().bsy().bit_is_set() {}
+    if flash.pecr.read().pelock().bit_is_set() {
+        flash.pekeyr.write(|w| w.pekeyr().bits(0x89AB_CDEF));
+        flash.pekeyr.write(|w| w.pekeyr().bits(0x0203_0405));
+    }
+
+    for (i, &word) in words.iter().enumerate() {
+        let target = address.add(i);
+        if target.read_volatile() != word {
+            target.write_volatile(word);
+            while flash.sr.read().bsy().bit_is_set() {}
+        }
+    }
+
+    // Lock again
+    flash.pecr.modify(|_, w| w.pelock().set_bit());
+}
+
+/// Pack a string into words, little-endian, padded with zeros
+#[cfg(feature = "panic-reboot")]
+fn pack_str(words: &mut [u32], s: &str) {
+    for (word, chunk) in words.iter_mut().zip(s.as_bytes().chunks(4)) {
+        let mut bytes = [0u8; 4];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        *word = u32::from_le_bytes(bytes);
+    }
+}
+
+/// Unpack a string packed by [`pack_str()`], stopping at padding or anything that isn't valid
+fn unpack_str<const N: usize>(words: &[u32]) -> ArrayString<N> {
+    let mut bytes = [0u8; N];
+    for (chunk, word) in bytes.chunks_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(N);
+    let s = match core::str::from_utf8(&bytes[..len]) {
+        Ok(s) => s,
+        // Truncated in the middle of a character, keep the valid part
+        Err(e) => core::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or("")
+    };
+    // Can't fail, as it's at most N bytes
+    ArrayString::from(s).unwrap_or_default()
+}
+
+/// The panic handler itself
+#[cfg(feature = "panic-reboot")]
+mod handler {
+    use core::fmt::Write;
+    use super::*;
+    use crate::util::Truncating;
+
+    /// Range of flash memory that return addresses can point into
+    const FLASH_RANGE: core::ops::Range<u32> = 0x0800_0000..0x0803_0000;
+    /// How many words up the stack to look for return addresses
+    const STACK_SCAN_WORDS: usize = 64;
+
+    /// Save a crash report and reset
+    #[panic_handler]
+    fn panic(info: &core::panic::PanicInfo) -> ! {
+        cortex_m::interrupt::disable();
+
+        let mut report = CrashReport {
+            file: ArrayString::new(),
+            line: 0,
+            message: ArrayString::new(),
+            sp: cortex_m::register::msp::read(),
+            backtrace: [0; BACKTRACE_LEN]
+        };
+
+        if let Some(location) = info.location() {
+            // Keep the end of the path, since that's the file name
+            let file = location.file();
+            let mut start = file.len().saturating_sub(FILE_LEN);
+            while !file.is_char_boundary(start) {
+                start += 1;
+            }
+            let _ = report.file.try_push_str(&file[start..]);
+            report.line = location.line();
+        }
+        let _ = write!(Truncating(&mut report.message), "{}", info.message());
+
+        // Look for return addresses: odd (Thumb) values that point into flash
+        extern "C" {
+            static _stack_start: u32;
+        }
+        // Safe because it's a linker symbol, only its address is used
+        let stack_end = unsafe { &_stack_start as *const u32 as u32 };
+        let mut found = 0;
+        let mut address = report.sp;
+        for _ in 0..STACK_SCAN_WORDS {
+            if found == BACKTRACE_LEN || address >= stack_end {
+                break;
+            }
+            // Safe because the address is between the stack pointer and the top of the stack
+            let value = unsafe { (address as *const u32).read_volatile() };
+            if value & 1 == 1 && FLASH_RANGE.contains(&value) {
+                report.backtrace[found] = value & !1;
+                found += 1;
+            }
+            address += 4;
+        }
+
+        // Safe because interrupts are disabled, so nothing else can be using the EEPROM, and the
+        // report area is in EEPROM
+        unsafe {
+            eeprom_write(&report.to_words(), CRASH_REPORT_ADDRESS as *mut u32);
+        }
+
+        cortex_m::peripheral::SCB::sys_reset()
+    }
+}
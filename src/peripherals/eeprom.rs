@@ -0,0 +1,213 @@
+//! Settings, waypoint and activity summary storage in the data EEPROM.
+//!
+//! Settings are written alternately to two slots, each record carrying a sequence number, so the
+//! newest valid one is used on boot. This halves the wear on each slot, and if power is lost
+//! mid-write the other slot still holds the previous settings. Waypoints and the summary of the
+//! last activity are rarely written, so they each just have one slot. Words that already hold the
+//! right value aren't rewritten, since each EEPROM write is an erase cycle.
+
+use stm32l0xx_hal::{
+    flash::{self, FLASH, EEPROM_START_BANK1, EEPROM_SIZE},
+    pac,
+    rcc::Rcc
+};
+
+use crate::state::settings::{Settings, SettingsError, RECORD_WORDS};
+use crate::state::waypoints::{self, Waypoint, WaypointError, WAYPOINT_COUNT};
+use crate::activity::{self, ActivitySummary};
+
+/// Bytes reserved for each record slot
+const SLOT_SIZE: usize = 64;
+/// Number of slots written in turn
+const SLOT_COUNT: usize = 2;
+const _: () = assert!(RECORD_WORDS * 4 <= SLOT_SIZE);
+/// Start of the area after the settings slots, reserved for the crash report (see `crash`)
+pub const CRASH_REPORT_ADDRESS: usize = EEPROM_START_BANK1 + SLOT_SIZE * SLOT_COUNT;
+/// Bytes reserved for the crash report
+pub const CRASH_REPORT_SIZE: usize = 128;
+/// Start of the waypoint slots, after the crash report
+const WAYPOINTS_ADDRESS: usize = CRASH_REPORT_ADDRESS + CRASH_REPORT_SIZE;
+/// Start of the last activity's summary, after the waypoints
+const ACTIVITY_ADDRESS: usize = WAYPOINTS_ADDRESS + WAYPOINT_COUNT * waypoints::RECORD_WORDS * 4;
+const _: () = assert!(ACTIVITY_ADDRESS + activity::RECORD_WORDS * 4 <= EEPROM_START_BANK1 + EEPROM_SIZE);
+
+/// Loads and saves [`Settings`] in EEPROM
+pub struct SettingsStore {
+    flash: FLASH,
+    /// Slot holding the newest record, or `None` if nothing valid has been saved
+    current: Option<usize>,
+    /// Sequence number of the newest record
+    seq: u32,
+    /// Settings as last loaded or saved
+    saved: Settings,
+    /// Why no settings were loaded, if none were
+    load_error: SettingsError,
+    /// Waypoints as last loaded or saved
+    waypoints: [Option<Waypoint>; WAYPOINT_COUNT],
+    /// Summary of the last activity as last loaded or saved
+    activity: Option<ActivitySummary>
+}
+impl core::fmt::Debug for SettingsStore {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SettingsStore")
+            .field("current", &self.current)
+            .field("seq", &self.seq)
+            .field("saved", &self.saved)
+            .field("load_error", &self.load_error)
+            .field("waypoints", &self.waypoints)
+            .field("activity", &self.activity)
+            .finish_non_exhaustive() // because FLASH doesn't have Debug
+    }
+}
+
+impl SettingsStore {
+    /// Find the newest valid settings record, and load the waypoints and last activity
+    pub fn new(flash: pac::FLASH, rcc: &mut Rcc) -> Self {
+        let mut store = Self {
+            flash: FLASH::new(flash, rcc),
+            current: None,
+            seq: 0,
+            saved: Settings::default(),
+            load_error: SettingsError::Blank,
+            waypoints: [None; WAYPOINT_COUNT],
+            activity: None
+        };
+
+        for slot in 0..SLOT_COUNT {
+            match Settings::from_record(&read_slot(slot)) {
+                Ok((seq, settings)) => {
+                    // Sequence numbers wrap, so compare by distance
+                    if store.current.is_none() || seq.wrapping_sub(store.seq) as i32 > 0 {
+                        store.current = Some(slot);
+                        store.seq = seq;
+                        store.saved = settings;
+                    }
+                }
+                Err(e) => {
+                    log::debug!("settings slot {}: {:?}", slot, e);
+                    store.load_error = store.load_error.max(e);
+                }
+            }
+        }
+
+        if let Some(slot) = store.current {
+            log::info!("loaded settings from slot {} (seq {})", slot, store.seq);
+        }
+
+        for (slot, waypoint) in store.waypoints.iter_mut().enumerate() {
+            match Waypoint::from_record(&read_words(waypoint_address(slot)), slot) {
+                Ok(w) => *waypoint = Some(w),
+                Err(WaypointError::Blank) => (),
+                Err(e) => log::warn!("waypoint slot {}: {:?}", slot, e)
+            }
+        }
+        store.activity = ActivitySummary::from_record(&read_words(ACTIVITY_ADDRESS as *mut u32));
+        store
+    }
+
+    /// The settings that were loaded, or why none could be
+    pub fn settings(&self) -> Result<Settings, SettingsError> {
+        match self.current {
+            Some(_) => Ok(self.saved),
+            None => Err(self.load_error)
+        }
+    }
+
+    /// Save settings if they've changed since they were last loaded or saved
+    pub fn save(&mut self, settings: &Settings) -> Result<(), flash::Error> {
+        if self.current.is_some() && *settings == self.saved {
+            return Ok(());
+        }
+
+        let slot = match self.current {
+            Some(slot) => (slot + 1) % SLOT_COUNT,
+            None => 0
+        };
+        let seq = self.seq.wrapping_add(1);
+        write_changed(&mut self.flash, slot_address(slot), &settings.to_record(seq))?;
+
+        log::info!("saved settings to slot {} (seq {})", slot, seq);
+        self.current = Some(slot);
+        self.seq = seq;
+        self.saved = *settings;
+        Ok(())
+    }
+
+    /// The waypoints that were loaded, with `None` for empty or damaged slots
+    pub fn waypoints(&self) -> [Option<Waypoint>; WAYPOINT_COUNT] {
+        self.waypoints
+    }
+
+    /// Save any waypoints that have changed since they were last loaded or saved. Removed ones
+    /// are erased.
+    pub fn save_waypoints(&mut self, waypoints: &[Option<Waypoint>; WAYPOINT_COUNT]) -> Result<(), flash::Error> {
+        for (slot, (new, old)) in waypoints.iter().zip(self.waypoints.iter_mut()).enumerate() {
+            if new == old {
+                continue;
+            }
+            let record = match new {
+                Some(waypoint) => waypoint.to_record(slot),
+                None => [0; waypoints::RECORD_WORDS]
+            };
+            write_changed(&mut self.flash, waypoint_address(slot), &record)?;
+            log::info!("saved waypoint slot {}: {:?}", slot, new);
+            *old = *new;
+        }
+        Ok(())
+    }
+
+    /// The summary of the last activity that was saved, if any
+    pub fn last_activity(&self) -> Option<ActivitySummary> {
+        self.activity
+    }
+
+    /// Save the summary of the last activity if it's changed since it was last loaded or saved
+    pub fn save_activity(&mut self, summary: &Option<ActivitySummary>) -> Result<(), flash::Error> {
+        if *summary == self.activity {
+            return Ok(());
+        }
+        let record = match summary {
+            Some(summary) => summary.to_record(),
+            None => [0; activity::RECORD_WORDS]
+        };
+        write_changed(&mut self.flash, ACTIVITY_ADDRESS as *mut u32, &record)?;
+        log::info!("saved activity summary: {:?}", summary);
+        self.activity = *summary;
+        Ok(())
+    }
+}
+
+fn slot_address(slot: usize) -> *mut u32 {
+    (EEPROM_START_BANK1 + slot * SLOT_SIZE) as *mut u32
+}
+
+fn waypoint_address(slot: usize) -> *mut u32 {
+    (WAYPOINTS_ADDRESS + slot * waypoints::RECORD_WORDS * 4) as *mut u32
+}
+
+fn read_slot(slot: usize) -> [u32; RECORD_WORDS] {
+    read_words(slot_address(slot))
+}
+
+/// Read a record from EEPROM
+fn read_words<const N: usize>(address: *mut u32) -> [u32; N] {
+    let mut record = [0u32; N];
+    for (i, word) in record.iter_mut().enumerate() {
+        // Safe, as all the records lie within the EEPROM, which is always readable
+        *word = unsafe { address.add(i).read_volatile() };
+    }
+    record
+}
+
+/// Write a record to EEPROM, skipping words that already hold the right value
+fn write_changed<const N: usize>(flash: &mut FLASH, address: *mut u32, record: &[u32; N]) -> Result<(), flash::Error> {
+    let existing = read_words::<N>(address);
+    // Words are written in order, so the CRC only goes in after everything it covers and a torn
+    // write can't look valid
+    for (i, (&new, &old)) in record.iter().zip(&existing).enumerate() {
+        if new != old {
+            flash.write_word(address.wrapping_add(i), new)?;
+        }
+    }
+    Ok(())
+}
@@ -4,23 +4,47 @@ use stm32l0xx_hal::{
     prelude::*,
     serial::{
         Serial,
-        Event as SerialEvent
+        Event as SerialEvent,
+        Error as SerialError
     },
     pac::{
         LPUART1
     }
 };
-use nmea0183::{
-    Parser,
-    ParseResult
-};
 use arrayvec::ArrayVec;
+use chrono::{NaiveDate, NaiveTime};
+
+use crate::error::{self, MainError};
+use crate::geo::Position;
+use crate::nmea::{NmeaParser, NmeaSentence, NmeaError, Coord, FixType, FixMode, SatelliteInView};
+use crate::peripherals::monotonic::{Instant, Duration};
+
+/// Most satellites in view that are kept track of
+pub const MAX_SATELLITES: usize = 24;
+/// How long a satellite is kept after it was last listed in view. Receivers send the list less
+/// often than fixes.
+const SATELLITE_TIMEOUT: Duration = Duration::secs(15);
+/// How long a satellite counts as used in the fix after it was last listed as used
+const USED_TIMEOUT: Duration = Duration::secs(3);
+
+/// Error counts since startup, to help debug reception
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct GpsErrorCounts {
+    pub framing: u16,
+    pub noise: u16,
+    pub overrun: u16,
+    pub parity: u16,
+    /// Sentences with a bad or missing checksum
+    pub checksum: u16,
+    /// Sentences that were cut off or couldn't be parsed
+    pub invalid: u16
+}
 
 pub struct Gps {
     uart: Serial<LPUART1>,
-    parser: Parser
+    parser: NmeaParser,
+    errors: GpsErrorCounts
 }
-
 impl Gps {
     pub fn new(mut uart: Serial<LPUART1>) -> Self {
         // Only send interrupts for Rx events
@@ -28,15 +52,36 @@ impl Gps {
         uart.unlisten(SerialEvent::Txe);
         uart.unlisten(SerialEvent::Idle);
 
+        // Wake the MCU from Stop mode when a byte is received. LPUART1 must be clocked from HSI16.
+        // Safe because we own the LPUART, and the HAL doesn't expose these bits
+        let regs = unsafe { &*LPUART1::ptr() };
+        // WUS can only be written while the LPUART is disabled
+        regs.cr1.modify(|_, w| w.ue().clear_bit());
+        regs.cr3.modify(|_, w| w.wus().rxne());
+        regs.cr1.modify(|_, w| w.uesm().set_bit().ue().set_bit());
+
         Self {
             uart,
-            parser: Parser::new()
+            parser: NmeaParser::new(),
+            errors: GpsErrorCounts::default()
+        }
+    }
+
+    /// Send a raw command (e.g. a proprietary NMEA sentence) to the receiver, blocking until sent.
+    pub fn send(&mut self, command: &[u8]) -> Result<(), MainError> {
+        for &b in command {
+            nb::block!(self.uart.write(b))?;
         }
+        Ok(())
+    }
+
+    /// Error counts since startup
+    pub fn errors(&self) -> GpsErrorCounts {
+        self.errors
     }
 
     /// Reads data from the GPS serial.
-    pub fn recv(&mut self) -> ArrayVec<ParseResult, 8> {
-        // todo!();
+    pub fn recv(&mut self) -> ArrayVec<NmeaSentence, 8> {
         let mut sentences = ArrayVec::new();
 
         loop {
@@ -45,9 +90,17 @@ impl Gps {
                 // If there's a byte to read, get it
                 Ok(b) => b,
                 Err(e) => match e {
-                    // If there's a UART error, log it and continue
+                    // If there's a UART error, report it and continue
                     NbError::Other(e) => {
-                        log::error!("UART error: {:?}", e);
+                        let count = match e {
+                            SerialError::Framing => &mut self.errors.framing,
+                            SerialError::Noise => &mut self.errors.noise,
+                            SerialError::Overrun => &mut self.errors.overrun,
+                            // Parity, the only other error so far
+                            _ => &mut self.errors.parity
+                        };
+                        *count = count.saturating_add(1);
+                        error::report(e.into());
                         continue
                     },
                     // If there's nothing left to read, done
@@ -56,9 +109,7 @@ impl Gps {
             };
 
             // If the parser has gotten enough data to parse a sentence (or error):
-            // if let Some(res) = self.parser.parse_from_byte(b) {
-            {
-                let res: Result<ParseResult, &str> = Err("foo");
+            if let Some(res) = self.parser.parse_from_byte(b) {
                 match res {
                     // If parse successful, add to sentence list
                     Ok(s) => {
@@ -70,8 +121,16 @@ impl Gps {
                             return sentences
                         }
                     },
-                    // Else, log parse error and move on
-                    Err(e) => log::error!("NMEA parse error: {:?}", e)
+                    // Else, count and log parse error and move on. Not reported, since the odd
+                    // corrupted sentence is expected with a weak signal.
+                    Err(e) => {
+                        let count = match e {
+                            NmeaError::MissingChecksum | NmeaError::BadChecksum => &mut self.errors.checksum,
+                            _ => &mut self.errors.invalid
+                        };
+                        *count = count.saturating_add(1);
+                        log::warn!("NMEA parse error: {:?}", e);
+                    }
                 }
             }
 
@@ -79,4 +138,164 @@ impl Gps {
 
         sentences
     }
-}
\ No newline at end of file
+}
+
+/// What the GPS last reported, put together from the different sentences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpsInfo {
+    pub fix_type: FixType,
+    pub fix_mode: FixMode,
+    /// Satellites used in the fix, or `None` if the receiver hasn't reported since it was turned on
+    pub satellites: Option<u8>,
+    /// Dilutions of precision, in tenths
+    pub hdop: u8,
+    pub pdop: u8,
+    pub latitude: Option<Coord>,
+    pub longitude: Option<Coord>,
+    /// Altitude above mean sea level in decimeters
+    pub altitude_dm: Option<i32>,
+    /// Speed over ground in hundredths of a knot, and course over ground in tenths of a degree
+    /// from true north
+    pub speed_cknots: Option<u32>,
+    pub course_ddeg: Option<u16>,
+    /// UTC time and date of the last sentence that had them
+    pub time: Option<NaiveTime>,
+    pub date: Option<NaiveDate>,
+    /// When the last valid fix was received
+    pub last_fix: Option<Instant>,
+    /// Satellites in view
+    pub sky: ArrayVec<Satellite, MAX_SATELLITES>,
+    pub errors: GpsErrorCounts
+}
+
+/// A satellite in view, and when it was last reported
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Satellite {
+    pub info: SatelliteInView,
+    /// When it was last listed in view
+    seen: Instant,
+    /// When it was last listed as used in the fix
+    used: Option<Instant>
+}
+
+impl Satellite {
+    /// Whether it's being used in the fix
+    pub fn is_used(&self, now: Instant) -> bool {
+        self.used.and_then(|t| now.checked_duration_since(t)).is_some_and(|d| d < USED_TIMEOUT)
+    }
+}
+
+impl Default for GpsInfo {
+    fn default() -> Self {
+        Self {
+            fix_type: FixType::Invalid,
+            fix_mode: FixMode::NoFix,
+            satellites: None,
+            hdop: u8::MAX,
+            pdop: u8::MAX,
+            latitude: None,
+            longitude: None,
+            altitude_dm: None,
+            speed_cknots: None,
+            course_ddeg: None,
+            time: None,
+            date: None,
+            last_fix: None,
+            sky: ArrayVec::new(),
+            errors: GpsErrorCounts::default()
+        }
+    }
+}
+
+impl GpsInfo {
+    /// Update from a received sentence. Returns whether it had a valid fix.
+    pub fn apply(&mut self, sentence: &NmeaSentence, now: Instant) -> bool {
+        let valid = match *sentence {
+            NmeaSentence::Gga { time, latitude, longitude, fix_type, satellites, hdop, altitude_dm } => {
+                self.time = time.or(self.time);
+                self.fix_type = fix_type;
+                self.satellites = Some(satellites);
+                self.hdop = hdop;
+                if fix_type.is_valid() {
+                    self.latitude = latitude;
+                    self.longitude = longitude;
+                    self.altitude_dm = altitude_dm;
+                }
+                fix_type.is_valid()
+            }
+            NmeaSentence::Gll { latitude, longitude, time, valid } => {
+                self.time = time.or(self.time);
+                if valid {
+                    self.latitude = latitude;
+                    self.longitude = longitude;
+                }
+                valid
+            }
+            NmeaSentence::Gsa { fix_mode, ref satellites, pdop, hdop, .. } => {
+                self.fix_mode = fix_mode;
+                self.pdop = pdop;
+                self.hdop = hdop;
+                // There's a GSA per constellation, so satellites stop counting as used by timing
+                // out rather than by being left off the next one
+                for satellite in self.sky.iter_mut().filter(|s| satellites.contains(&s.info.prn)) {
+                    satellite.used = Some(now);
+                }
+                false
+            }
+            NmeaSentence::Gsv { ref satellites, .. } => {
+                // Each constellation sends its own list, so keep track of them all together
+                self.sky.retain(|s| now.checked_duration_since(s.seen).is_none_or(|d| d < SATELLITE_TIMEOUT));
+                for &info in satellites {
+                    if let Some(satellite) = self.sky.iter_mut().find(|s| s.info.prn == info.prn) {
+                        satellite.info = info;
+                        satellite.seen = now;
+                        continue;
+                    }
+                    let satellite = Satellite { info, seen: now, used: None };
+                    if let Err(e) = self.sky.try_push(satellite) {
+                        // Full, so replace the one that's been seen least recently
+                        if let Some(oldest) = self.sky.iter_mut().min_by_key(|s| s.seen) {
+                            *oldest = e.element();
+                        }
+                    }
+                }
+                false
+            }
+            NmeaSentence::Rmc { time, date, valid, latitude, longitude, speed_cknots, course_ddeg } => {
+                self.time = time.or(self.time);
+                self.date = date.or(self.date);
+                if valid {
+                    self.latitude = latitude;
+                    self.longitude = longitude;
+                    self.speed_cknots = speed_cknots;
+                    self.course_ddeg = course_ddeg;
+                }
+                valid
+            }
+        };
+        if valid {
+            self.last_fix = Some(now);
+        }
+        valid
+    }
+
+    /// Forget the current fix, e.g. because the receiver was turned off. The time of the last fix
+    /// and the error counts are kept.
+    pub fn clear(&mut self) {
+        *self = Self {
+            last_fix: self.last_fix,
+            errors: self.errors,
+            ..Self::default()
+        };
+    }
+
+    /// Whether the receiver currently has a fix
+    pub fn has_fix(&self) -> bool {
+        self.fix_type.is_valid()
+    }
+
+    /// The last reported position, if there's been a fix
+    pub fn position(&self) -> Option<Position> {
+        Some(Position::from_coords(self.latitude.as_ref()?, self.longitude.as_ref()?))
+    }
+}
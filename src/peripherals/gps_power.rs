@@ -0,0 +1,240 @@
+//! GPS power policy: decides when the receiver is on, in standby, or off.
+//!
+//! The receiver has two low-power states:
+//! - Standby, entered with a command over the UART and left by sending any byte. Everything is
+//!   kept in RAM, so the next fix is a hot start.
+//! - Backup, entered by pulling the enable pin low. Only the backup supply (V_BCKP) stays on, which
+//!   keeps the receiver's RTC and ephemeris, so a hot start is still possible if the last fix was
+//!   recent enough.
+//!
+//! Commands are for MediaTek (PMTK) receivers.
+
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::peripherals::{
+    Gps,
+    monotonic::{Instant, Duration, TIMER_HZ}
+};
+use crate::error::MainError;
+
+/// PMTK161: enter standby mode
+const COMMAND_STANDBY: &[u8] = b"$PMTK161,0*28\r\n";
+/// PMTK000: test packet, sent to wake the receiver from standby
+const COMMAND_WAKE: &[u8] = b"$PMTK000*32\r\n";
+
+/// How long ephemeris data is assumed to be usable for a hot start
+const EPHEMERIS_VALID: Duration = Duration::hours(2);
+/// How long to wait for a fix before giving up and going back to sleep
+const ACQUIRE_TIMEOUT: Duration = Duration::minutes(5);
+/// How long to stay on after a hot-start fix, to get a few good fixes for time sync
+const HOLD_HOT: Duration = Duration::secs(5);
+/// How long to stay on after a warm- or cold-start fix, to download the full ephemeris (which is
+/// broadcast every 30 s) so the next start is hot
+const HOLD_COLD: Duration = Duration::secs(40);
+
+/// What the GPS should be doing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GpsPowerMode {
+    /// Always off (backup mode)
+    Off,
+    /// Always on, e.g. while recording a track
+    Continuous,
+    /// Wake up every `interval_min` minutes to get a fix, e.g. for time sync only
+    Periodic {
+        interval_min: u16
+    }
+}
+
+/// What kind of start the receiver will do, based on how long ago the last fix was
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StartType {
+    /// Ephemeris is still valid: fix in a few seconds
+    Hot,
+    /// Time and approximate position known, but ephemeris needs to be downloaded again
+    Warm,
+    /// Nothing known
+    Cold
+}
+
+/// Current state of the receiver
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ReceiverState {
+    /// Powered off by the enable pin
+    Off,
+    /// Put in standby by a command
+    Standby {
+        /// When to wake up again
+        wake_at: Option<Instant>
+    },
+    /// On and acquiring or tracking
+    On {
+        since: Instant,
+        start: StartType,
+        /// When the first fix since powering on was acquired
+        fix_at: Option<Instant>
+    }
+}
+
+/// Controls GPS receiver power according to a [`GpsPowerMode`]
+pub struct GpsPower<EN> {
+    enable: EN,
+    mode: GpsPowerMode,
+    /// Stay on even if the mode is periodic, e.g. while recording a track
+    keep_on: bool,
+    state: ReceiverState,
+    last_fix: Option<Instant>
+}
+impl<EN> core::fmt::Debug for GpsPower<EN> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GpsPower")
+            .field("mode", &self.mode)
+            .field("keep_on", &self.keep_on)
+            .field("state", &self.state)
+            .field("last_fix", &self.last_fix)
+            .finish_non_exhaustive() // because the pin doesn't have Debug
+    }
+}
+
+impl<EN: OutputPin> GpsPower<EN> {
+    /// Create the power policy. The receiver is turned off until the first [`GpsPower::update()`].
+    pub fn new(mut enable: EN, mode: GpsPowerMode) -> Self {
+        let _ = enable.set_low(); // cannot error
+
+        Self {
+            enable,
+            mode,
+            keep_on: false,
+            state: ReceiverState::Off,
+            last_fix: None
+        }
+    }
+
+    /// Change the power mode. Takes effect on the next [`GpsPower::update()`].
+    pub fn set_mode(&mut self, mode: GpsPowerMode) {
+        self.mode = mode;
+        // Re-evaluate standby immediately instead of waiting out the old interval
+        if let ReceiverState::Standby { wake_at } = &mut self.state {
+            *wake_at = None;
+        }
+    }
+
+    /// Keep the receiver on in [`GpsPowerMode::Periodic`], as if it were continuous. Doesn't
+    /// override [`GpsPowerMode::Off`], since that's only used when the battery is nearly flat.
+    pub fn set_keep_on(&mut self, keep_on: bool) {
+        if keep_on != self.keep_on {
+            log::info!("GPS kept on: {}", keep_on);
+            self.keep_on = keep_on;
+        }
+    }
+
+    /// The current power mode
+    pub fn mode(&self) -> GpsPowerMode {
+        self.mode
+    }
+
+    /// Whether the receiver is currently powered and acquiring or tracking
+    pub fn is_on(&self) -> bool {
+        matches!(self.state, ReceiverState::On { .. })
+    }
+
+    /// What kind of start the receiver would do if it were turned on now
+    pub fn start_type(&self, now: Instant) -> StartType {
+        match self.last_fix.and_then(|t| now.checked_duration_since(t)) {
+            Some(age) if age < EPHEMERIS_VALID => StartType::Hot,
+            Some(_) => StartType::Warm,
+            None => StartType::Cold
+        }
+    }
+
+    /// Should be called whenever a valid fix is received
+    pub fn fix_acquired(&mut self, now: Instant) {
+        self.last_fix = Some(now);
+        if let ReceiverState::On { fix_at: fix_at @ None, .. } = &mut self.state {
+            *fix_at = Some(now);
+        }
+    }
+
+    /// Apply the power mode. Should be called periodically (about once per second). If sending a
+    /// command to the receiver fails, it's retried on the next update.
+    pub fn update(&mut self, gps: &mut Gps, now: Instant) -> Result<(), MainError> {
+        let mode = match self.mode {
+            GpsPowerMode::Periodic { .. } if self.keep_on => GpsPowerMode::Continuous,
+            mode => mode
+        };
+        match (mode, self.state) {
+            // Off: keep only the backup supply on
+            (GpsPowerMode::Off, ReceiverState::Off) => (),
+            (GpsPowerMode::Off, _) => self.power_off(),
+
+            // Continuous: keep on
+            (GpsPowerMode::Continuous, ReceiverState::On { .. }) => (),
+            (GpsPowerMode::Continuous, _) => self.power_on(gps, now)?,
+
+            // Periodic: sleep until the interval has passed, then stay on until a fix is acquired
+            (GpsPowerMode::Periodic { interval_min }, ReceiverState::On { since, start, fix_at }) => {
+                let hold = if start == StartType::Hot { HOLD_HOT } else { HOLD_COLD };
+                let done = match fix_at {
+                    Some(fix_at) => now.checked_duration_since(fix_at).is_some_and(|d| d >= hold),
+                    None => {
+                        let timed_out = now.checked_duration_since(since).is_some_and(|d| d >= ACQUIRE_TIMEOUT);
+                        if timed_out {
+                            log::warn!("no GPS fix after {} s, giving up", ACQUIRE_TIMEOUT.ticks() / TIMER_HZ as u64);
+                        }
+                        timed_out
+                    }
+                };
+                if done {
+                    self.standby(gps, now + Duration::minutes(interval_min as u64))?;
+                }
+            },
+            (GpsPowerMode::Periodic { .. }, ReceiverState::Standby { wake_at }) => {
+                if wake_at.is_none_or(|t| now >= t) {
+                    self.power_on(gps, now)?;
+                }
+            },
+            (GpsPowerMode::Periodic { .. }, ReceiverState::Off) => self.power_on(gps, now)?
+        }
+        Ok(())
+    }
+
+    /// Turn the receiver on, from backup or standby
+    fn power_on(&mut self, gps: &mut Gps, now: Instant) -> Result<(), MainError> {
+        let start = self.start_type(now);
+        log::info!("GPS on ({:?} start)", start);
+
+        match self.state {
+            ReceiverState::Off => { let _ = self.enable.set_high(); },
+            ReceiverState::Standby { .. } => gps.send(COMMAND_WAKE)?,
+            ReceiverState::On { .. } => return Ok(())
+        }
+        self.state = ReceiverState::On {
+            since: now,
+            start,
+            fix_at: None
+        };
+        Ok(())
+    }
+
+    /// Put the receiver in standby until `wake_at`
+    fn standby(&mut self, gps: &mut Gps, wake_at: Instant) -> Result<(), MainError> {
+        log::info!("GPS standby");
+
+        if self.state == ReceiverState::Off {
+            // Has to be on to receive the command
+            let _ = self.enable.set_high();
+        }
+        gps.send(COMMAND_STANDBY)?;
+        self.state = ReceiverState::Standby {
+            wake_at: Some(wake_at)
+        };
+        Ok(())
+    }
+
+    /// Turn the receiver off, keeping only the backup supply
+    fn power_off(&mut self) {
+        log::info!("GPS off");
+
+        let _ = self.enable.set_low();
+        self.state = ReceiverState::Off;
+    }
+}
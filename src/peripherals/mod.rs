@@ -1,9 +1,22 @@
 //! Various wrappers for peripherals
 
-pub mod display;
+#[allow(dead_code)] // Alerts aren't wired up to anything yet
 pub mod alert;
 pub mod gps;
+pub mod gps_power;
+pub mod rtc;
+pub mod power;
+pub mod monotonic;
+pub mod battery;
+pub mod eeprom;
+pub mod reset;
+pub mod crash;
 
-pub use display::SharpLcd;
+pub use gps_watch_core::display;
+
+pub use display::{SharpLcd, VcomMode, Rotation};
 pub use alert::Buzzer;
-pub use gps::Gps;
\ No newline at end of file
+pub use gps::Gps;
+pub use gps_power::{GpsPower, GpsPowerMode};
+pub use battery::{Battery, BatteryLevel};
+pub use eeprom::SettingsStore;
\ No newline at end of file
@@ -0,0 +1,171 @@
+//! An RTIC [`Monotonic`] based on LPTIM1 clocked from the LSE.
+//!
+//! Unlike SysTick, LPTIM1 keeps counting in Stop mode, and its interrupt can wake the MCU, so
+//! scheduled tasks stay on time while sleeping.
+//!
+//! The 16-bit counter is extended to 64 bits by counting auto-reload matches in software.
+//! See section 23 (LPTIM) of the [reference manual](https://www.st.com/resource/en/reference_manual/rm0377-ultralowpower-stm32l0x1-advanced-armbased-32bit-mcus-stmicroelectronics.pdf).
+
+use rtic_monotonic::Monotonic;
+use stm32l0xx_hal::{
+    pac,
+    rcc::{Rcc, LSE}
+};
+
+/// Counter frequency: the LSE with no prescaler
+pub const TIMER_HZ: u32 = 32_768;
+
+/// Instant type of [`LptimMonotonic`]
+pub type Instant = fugit::TimerInstantU64<TIMER_HZ>;
+/// Duration type of [`LptimMonotonic`]
+pub type Duration = fugit::TimerDurationU64<TIMER_HZ>;
+
+/// Milliseconds since the timer started, for code that doesn't deal in [`Instant`]s
+pub fn millis(instant: Instant) -> u64 {
+    instant.ticks() * 1000 / TIMER_HZ as u64
+}
+
+/// Minimum number of ticks between now and a compare match, so the match isn't missed while the
+/// CMP write is synchronized to the LPTIM clock
+const MIN_COMPARE_TICKS: u16 = 3;
+
+/// Monotonic timer using LPTIM1, with ~30 µs precision
+pub struct LptimMonotonic {
+    lptim: pac::LPTIM,
+    /// Number of auto-reload matches that have been handled
+    overflows: u32,
+    /// Whether a CMP write hasn't been acknowledged yet
+    cmp_pending: bool
+}
+
+impl LptimMonotonic {
+    /// Set up LPTIM1 to count the LSE. The counter is started by RTIC after `init`.
+    pub fn new(lptim: pac::LPTIM, _rcc: &mut Rcc, _lse: &LSE) -> Self {
+        // Safe because we hold the `Rcc`, and the HAL doesn't touch these bits
+        let rcc = unsafe { &*pac::RCC::ptr() };
+        // Clock from LSE, so it runs in Stop mode
+        rcc.ccipr.modify(|_, w| w.lptim1sel().lse());
+        rcc.apb1enr.modify(|_, w| w.lptim1en().set_bit());
+        rcc.apb1rstr.modify(|_, w| w.lptim1rst().set_bit());
+        rcc.apb1rstr.modify(|_, w| w.lptim1rst().clear_bit());
+
+        // Internal clock with no prescaler, and the registers not preloaded so CMP changes apply
+        // immediately. Reset values, but be explicit about it.
+        lptim.cfgr.modify(|_, w| {
+            w.cksel().clear_bit();
+            w.presc().bits(0);
+            w.preload().clear_bit()
+        });
+        // Interrupts can only be configured while disabled
+        lptim.ier.write(|w| w.arrmie().set_bit().cmpmie().set_bit());
+
+        Self {
+            lptim,
+            overflows: 0,
+            cmp_pending: false
+        }
+    }
+
+    /// Read the counter. It's clocked asynchronously, so it has to be read until two reads match.
+    fn read_cnt(&self) -> u16 {
+        loop {
+            let a = self.lptim.cnt.read().cnt().bits();
+            let b = self.lptim.cnt.read().cnt().bits();
+            if a == b {
+                return a;
+            }
+        }
+    }
+
+    /// Write the compare register, waiting for the previous write to finish if needed
+    fn write_cmp(&mut self, cmp: u16) {
+        if self.cmp_pending {
+            while self.lptim.isr.read().cmpok().bit_is_clear() {}
+        }
+        self.lptim.icr.write(|w| w.cmpokcf().set_bit());
+        self.lptim.cmp.write(|w| w.cmp().bits(cmp));
+        self.cmp_pending = true;
+    }
+}
+
+// Time is counted so that the auto-reload match at CNT = 0xFFFF starts a new 0x10000-tick period.
+// So tick `t` is at CNT = (t - 1) & 0xFFFF, in counter cycle (t - 1) >> 16.
+impl Monotonic for LptimMonotonic {
+    // Overflows have to be counted even if nothing is scheduled
+    const DISABLE_INTERRUPT_ON_EMPTY_QUEUE: bool = false;
+
+    type Instant = Instant;
+    type Duration = Duration;
+
+    fn now(&mut self) -> Self::Instant {
+        // Make sure the counter is read in the same period as the match flag
+        let (matched, cnt) = loop {
+            let before = self.lptim.isr.read().arrm().bit_is_set();
+            let cnt = self.read_cnt();
+            let after = self.lptim.isr.read().arrm().bit_is_set();
+            if before == after {
+                // The flag may not be visible yet at CNT = 0xFFFF
+                break (after || cnt == 0xFFFF, cnt);
+            }
+        };
+
+        let periods = self.overflows as u64 + matched as u64;
+        Self::Instant::from_ticks((periods << 16) + (cnt.wrapping_add(1) as u64))
+    }
+
+    fn set_compare(&mut self, instant: Self::Instant) {
+        let now = self.now().ticks();
+        let target = instant.ticks();
+
+        let cmp = if target.saturating_sub(1) >> 16 == now.saturating_sub(1) >> 16 {
+            // In this counter cycle: keep it far enough in the future to not be missed
+            let now_cnt = now.wrapping_sub(1) as u16;
+            let target_cnt = target.wrapping_sub(1) as u16;
+            target_cnt.max(now_cnt.saturating_add(MIN_COMPARE_TICKS))
+        }
+        else if target > now {
+            // In a later cycle: the auto-reload match interrupt will set it again
+            0xFFFF
+        }
+        else {
+            // Already passed: fire as soon as possible
+            (now.wrapping_sub(1) as u16).saturating_add(MIN_COMPARE_TICKS)
+        };
+
+        self.write_cmp(cmp);
+    }
+
+    fn clear_compare_flag(&mut self) {
+        self.lptim.icr.write(|w| w.cmpmcf().set_bit());
+    }
+
+    fn zero() -> Self::Instant {
+        Self::Instant::from_ticks(0)
+    }
+
+    unsafe fn reset(&mut self) {
+        self.lptim.cr.modify(|_, w| w.enable().set_bit());
+
+        // ARR can only be written while enabled
+        self.lptim.arr.write(|w| w.arr().bits(0xFFFF));
+        while self.lptim.isr.read().arrok().bit_is_clear() {}
+        self.lptim.icr.write(|w| w.arrokcf().set_bit());
+        self.write_cmp(0xFFFF);
+
+        // Start counting continuously
+        self.lptim.cr.modify(|_, w| w.cntstrt().set_bit());
+    }
+
+    fn on_interrupt(&mut self) {
+        if self.lptim.isr.read().arrm().bit_is_set() {
+            // Wait for the counter to move on from 0xFFFF, otherwise `now()` would count this
+            // match twice. Takes at most one tick.
+            while self.read_cnt() == 0xFFFF {}
+
+            self.lptim.icr.write(|w| w.arrmcf().set_bit());
+            // Clearing is synchronized to the LPTIM clock too
+            while self.lptim.isr.read().arrm().bit_is_set() {}
+            self.overflows += 1;
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Low-power mode configuration.
+//!
+//! The HAL's [`StopMode`](stm32l0xx_hal::pwr::StopMode) configures Stop mode and enters it in one
+//! go, which doesn't fit with RTIC's idle task (it would need to lock `rcc` and `pwr` around the
+//! `WFI`). Instead, Stop mode is configured once here, and the idle task just executes `WFI`.
+//!
+//! See section 6.3.9 of the [reference manual](https://www.st.com/resource/en/reference_manual/rm0377-ultralowpower-stm32l0x1-advanced-armbased-32bit-mcus-stmicroelectronics.pdf).
+
+use stm32l0xx_hal::{
+    pac,
+    pwr::PWR,
+    rcc::Rcc
+};
+use cortex_m::peripheral::SCB;
+
+/// Make every `WFI` enter Stop mode instead of Sleep mode.
+///
+/// In Stop mode all clocks except the LSE and LSI are stopped, so only the RTC, LPTIM1 and
+/// LPUART1 (when clocked from HSI16 or LSE) keep running, and can wake the MCU through EXTI.
+/// The system clock is restored to HSI16 on wakeup.
+pub fn configure_stop_mode(scb: &mut SCB, _rcc: &mut Rcc, _pwr: &mut PWR) {
+    // Safe because we hold the `Rcc` and `PWR`, and only modify bits the HAL doesn't use after init
+    let rcc = unsafe { &*pac::RCC::ptr() };
+    let pwr = unsafe { &*pac::PWR::ptr() };
+
+    // Wake up on HSI16, same as the clock configured in `init`
+    rcc.cfgr.modify(|_, w| w.stopwuck().hsi16());
+
+    pwr.cr.modify(|_, w| {
+        // Turn off VREFINT in Stop mode, and don't wait for it to start up on wakeup
+        w.ulp().enabled();
+        w.fwu().enabled();
+        // Clear wakeup flag
+        w.cwuf().set_bit();
+        // Stop mode, not Standby mode, with the voltage regulator in low-power mode
+        w.pdds().stop_mode();
+        w.lpds().low_power_mode()
+    });
+
+    // Keep the debugger connected in Stop mode
+    #[cfg(debug_assertions)]
+    {
+        rcc.apb2enr.modify(|_, w| w.dbgen().set_bit());
+        let dbg = unsafe { &*pac::DBG::ptr() };
+        dbg.cr.modify(|_, w| w.dbg_stop().set_bit());
+    }
+
+    scb.set_sleepdeep();
+}
+
+/// Clock LPUART1 from HSI16 instead of APB1. Must be called before the LPUART is configured.
+///
+/// HSI16 is woken up by the LPUART on demand in Stop mode, so it can keep receiving and wake the
+/// MCU. Since the system clock is HSI16 with no APB1 prescaler, the HAL's baud rate calculation
+/// is still correct.
+pub fn clock_lpuart_from_hsi16(_rcc: &mut Rcc) {
+    // Safe because we hold the `Rcc`, and the HAL doesn't touch LPUART1SEL
+    let rcc = unsafe { &*pac::RCC::ptr() };
+    rcc.ccipr.modify(|_, w| w.lpuart1sel().hsi16());
+}
+
+/// Wait for an interrupt in Stop mode, as configured by [`configure_stop_mode()`]
+#[inline(always)]
+pub fn stop() {
+    cortex_m::asm::dsb();
+    cortex_m::asm::wfi();
+}
@@ -0,0 +1,114 @@
+//! Reset supervision: the independent watchdog, and working out why the last reset happened.
+
+use stm32l0xx_hal::{
+    pac,
+    rcc::Rcc,
+    watchdog::{IndependedWatchdog, IndependedWatchdogExt}
+};
+
+/// IWDG prescaler setting: divide the LSI by 256
+const IWDG_PRESCALER: u8 = 0b110;
+/// IWDG reload value: the maximum. The LSI is only accurate to 26-56 kHz, so with the prescaler
+/// this times out after somewhere between 18.7 s and 40 s.
+const IWDG_RELOAD: u16 = 0xFFF;
+
+/// Why the MCU was last reset, from the flags in RCC_CSR
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResetReason {
+    /// Power-on, or brownout (the BOR shares this flag)
+    PowerOn,
+    /// The NRST pin was pulled low externally
+    Pin,
+    /// Software reset, e.g. after a panic
+    Software,
+    /// The independent watchdog wasn't fed in time
+    Watchdog,
+    /// The window watchdog wasn't fed in its window
+    WindowWatchdog,
+    /// Entered Stop or Standby mode when the option bytes forbid it
+    LowPower,
+    /// Option bytes were reloaded
+    OptionBytes,
+    /// The firewall was violated
+    Firewall,
+    /// No flags set
+    Unknown
+}
+
+impl ResetReason {
+    /// Read the reset flags then clear them, so the next reset's flags aren't mixed up with these
+    pub fn read_and_clear(_rcc: &mut Rcc) -> Self {
+        // Safe because we hold the `Rcc`, and the HAL doesn't use the reset flags
+        let rcc = unsafe { &*pac::RCC::ptr() };
+        let csr = rcc.csr.read();
+
+        // Every reset also pulls NRST low internally and sets PINRSTF, so check that last
+        let reason = if csr.porrstf().bit_is_set() {
+            Self::PowerOn
+        }
+        else if csr.iwdgrstf().bit_is_set() {
+            Self::Watchdog
+        }
+        else if csr.wwdgrstf().bit_is_set() {
+            Self::WindowWatchdog
+        }
+        else if csr.lpwrrstf().bit_is_set() {
+            Self::LowPower
+        }
+        else if csr.sftrstf().bit_is_set() {
+            Self::Software
+        }
+        else if csr.oblrstf().bit_is_set() {
+            Self::OptionBytes
+        }
+        else if csr.fwrstf().bit_is_set() {
+            Self::Firewall
+        }
+        else if csr.pinrstf().bit_is_set() {
+            Self::Pin
+        }
+        else {
+            Self::Unknown
+        };
+
+        rcc.csr.modify(|_, w| w.rmvf().set_bit());
+        reason
+    }
+
+    /// Whether the reset was unexpected, and worth telling the user about
+    pub fn is_abnormal(&self) -> bool {
+        !matches!(self, Self::PowerOn | Self::Pin)
+    }
+
+    /// Short name to show on screen
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PowerOn => "power on",
+            Self::Pin => "reset pin",
+            Self::Software => "software",
+            Self::Watchdog => "watchdog",
+            Self::WindowWatchdog => "window watchdog",
+            Self::LowPower => "low power",
+            Self::OptionBytes => "option bytes",
+            Self::Firewall => "firewall",
+            Self::Unknown => "unknown"
+        }
+    }
+}
+
+/// Start the independent watchdog. It must be fed at least every 18 s from then on, and can't be
+/// stopped. It keeps running in Stop mode, but is frozen while the core is halted by a debugger.
+pub fn start_watchdog(iwdg: pac::IWDG, _rcc: &mut Rcc) -> IndependedWatchdog {
+    #[cfg(debug_assertions)]
+    {
+        // Safe because we hold the `Rcc`, and only the IWDG freeze bit is touched
+        let rcc = unsafe { &*pac::RCC::ptr() };
+        rcc.apb2enr.modify(|_, w| w.dbgen().set_bit());
+        let dbg = unsafe { &*pac::DBG::ptr() };
+        dbg.apb1_fz.modify(|_, w| w.dbg_iwdg_stop().set_bit());
+    }
+
+    let mut watchdog = iwdg.watchdog();
+    watchdog.set_config(IWDG_PRESCALER, IWDG_RELOAD);
+    watchdog
+}
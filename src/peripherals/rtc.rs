@@ -0,0 +1,94 @@
+//! Extras for the HAL's [`Rtc`](stm32l0xx_hal::rtc::Rtc) that it doesn't expose itself.
+//!
+//! These access the RTC registers directly, so they take a `&mut Rtc` to make sure nothing else is
+//! using the RTC at the same time.
+
+use stm32l0xx_hal::{
+    pac,
+    prelude::*,
+    rtc::{Rtc, Interrupts}
+};
+
+/// Disable RTC write protection, run the passed in function, then re-enable write protection.
+/// Same as the HAL's private `Rtc::write()`.
+fn write<F, R>(_rtc: &mut Rtc, f: F) -> R where F: FnOnce(&pac::rtc::RegisterBlock) -> R {
+    // Safe because we hold the `Rtc`, so nothing else is accessing the registers
+    let regs = unsafe { &*pac::RTC::ptr() };
+
+    // Disable write protection
+    regs.wpr.write(|w| w.key().bits(0xca));
+    regs.wpr.write(|w| w.key().bits(0x53));
+
+    let res = f(regs);
+
+    // Re-enable write protection
+    regs.wpr.write(|w| w.key().bits(0xff));
+
+    res
+}
+
+/// Start the wakeup timer with its interrupt, firing every second.
+///
+/// Must be called again after [`Rtc::set()`], which resets the RTC's control register and with it
+/// the wakeup timer and interrupt enables.
+pub fn start_wakeup(rtc: &mut Rtc) {
+    rtc.enable_interrupts(Interrupts {
+        timestamp: false,
+        wakeup_timer: true,
+        alarm_a: false,
+        alarm_b: false
+    });
+    // The delay is in seconds
+    rtc.wakeup_timer().start(1u32);
+}
+
+/// Marks the backup registers as holding a [`BackupState`] ("WATC")
+const BACKUP_MAGIC: u32 = 0x5741_5443;
+/// Backup register indices
+const BKP_MAGIC: usize = 0;
+const BKP_LAST_SYNC: usize = 1;
+const BKP_MODE: usize = 2;
+
+/// State kept in the RTC backup registers.
+///
+/// These are in the same domain as the RTC, so they survive any reset that the RTC's time survives
+/// (watchdog, brownout, software reset), but not losing power entirely. If they're valid after a
+/// reset, the time is still valid too.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BackupState {
+    /// When the time was last set from GPS, as a Unix timestamp
+    pub last_sync: Option<u32>,
+    /// ID of the UI mode that was active
+    pub mode: u8
+}
+
+/// Read the state from the backup registers, or `None` if they don't hold any (e.g. after a
+/// power loss, when the RTC's time is no longer valid either)
+pub fn read_backup(_rtc: &mut Rtc) -> Option<BackupState> {
+    // Safe because we hold the `Rtc`, and only read
+    let regs = unsafe { &*pac::RTC::ptr() };
+
+    if regs.bkpr[BKP_MAGIC].read().bits() != BACKUP_MAGIC {
+        return None;
+    }
+    let last_sync = regs.bkpr[BKP_LAST_SYNC].read().bits();
+    Some(BackupState {
+        last_sync: if last_sync == 0 { None } else { Some(last_sync) },
+        mode: regs.bkpr[BKP_MODE].read().bits() as u8
+    })
+}
+
+/// Write the state to the backup registers
+pub fn write_backup(rtc: &mut Rtc, state: &BackupState) {
+    // Backup registers aren't covered by RTC write protection, only PWR's DBP bit, which the HAL
+    // leaves set. The `write()` wrapper is still used since it's harmless and keeps accesses
+    // consistent.
+    write(rtc, |regs| {
+        // Invalidate while writing, so a reset part way through can't leave a valid magic value
+        // next to a mix of old and new values
+        regs.bkpr[BKP_MAGIC].write(|w| w.bkp().bits(0));
+        regs.bkpr[BKP_LAST_SYNC].write(|w| w.bkp().bits(state.last_sync.unwrap_or(0)));
+        regs.bkpr[BKP_MODE].write(|w| w.bkp().bits(state.mode as u32));
+        regs.bkpr[BKP_MAGIC].write(|w| w.bkp().bits(BACKUP_MAGIC));
+    });
+}
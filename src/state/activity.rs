@@ -0,0 +1,126 @@
+//! Activity screen: elapsed time, distance, speed and pace of the activity being recorded, or a
+//! summary of the last one.
+
+use core::fmt::Write;
+
+use arrayvec::ArrayString;
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Rectangle, PrimitiveStyle},
+    text::Alignment
+};
+
+use crate::state::{self, UiMode, SharedState, Resources};
+use crate::activity::{self as rec, ActivityState};
+use crate::geo;
+use crate::peripherals::monotonic;
+use crate::widgets::{Dirty, Font, Label};
+
+/// Characters that fit on a line
+const LINE_CHARS: usize = 28;
+/// Number of lines under the elapsed time
+const LINES: usize = 4;
+/// Height of a line of text, with a gap between lines
+const LINE_HEIGHT: u32 = 12;
+
+type Line = Label<LINE_CHARS>;
+
+#[derive(Debug)]
+pub struct ActivityMode {
+    title: Dirty<Line>,
+    elapsed: Dirty<Label<12>>,
+    lines: [Dirty<Line>; LINES],
+    /// Whether the screen has been cleared since something else drew over it
+    cleared: bool
+}
+
+impl ActivityMode {
+    pub fn new() -> Self {
+        Self {
+            title: Dirty::new(Line::new("", Font::Medium).aligned(Alignment::Center)),
+            elapsed: Dirty::new(Label::new("", Font::Large).aligned(Alignment::Center)),
+            lines: core::array::from_fn(|_| Dirty::new(Line::new("", Font::Medium))),
+            cleared: false
+        }
+    }
+
+    /// Redraw everything on the next draw, since something else drew over the screen
+    pub fn invalidate(&mut self) {
+        self.cleared = false;
+    }
+
+    pub fn update(&mut self, _resources: &mut Resources, _shared_state: &mut SharedState) -> Option<UiMode> {
+        None
+    }
+
+    pub fn draw(&mut self, resources: &mut Resources, shared_state: &SharedState) {
+        let display = &mut resources.display;
+        let area = state::content_area(display);
+        if !self.cleared {
+            let _ = area.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off)).draw(display);
+            self.title.invalidate();
+            self.elapsed.invalidate();
+            for line in &mut self.lines {
+                line.invalidate();
+            }
+            self.cleared = true;
+        }
+
+        let (title, elapsed, texts) = contents(shared_state);
+        self.title.update(|label| label.set_text(title));
+        self.elapsed.update(|label| label.set_text(&elapsed));
+
+        let width = area.size.width - 1;
+        let mut top_left = area.top_left + Point::new(1, 0);
+        self.title.draw(display, Rectangle::new(top_left, Size::new(width, LINE_HEIGHT)));
+        top_left.y += LINE_HEIGHT as i32;
+        let elapsed_height = Font::Large.height() + 4;
+        self.elapsed.draw(display, Rectangle::new(top_left, Size::new(width, elapsed_height)));
+        top_left.y += elapsed_height as i32;
+
+        // Only lines that changed are redrawn
+        for (line, text) in self.lines.iter_mut().zip(&texts) {
+            line.update(|label| label.set_text(text));
+            line.draw(display, Rectangle::new(top_left, Size::new(width, LINE_HEIGHT)));
+            top_left.y += LINE_HEIGHT as i32;
+        }
+    }
+}
+
+/// The title, elapsed time and the lines under it. Lines are short enough that formatting can't
+/// fail.
+fn contents(shared_state: &SharedState) -> (&'static str, ArrayString<12>, [ArrayString<LINE_CHARS>; LINES]) {
+    let units = shared_state.settings.units;
+    let activity = &shared_state.activity;
+    let mut lines = [ArrayString::new(); LINES];
+
+    let (title, distance_m, elapsed_s, moving_s, average) = match (activity.state(), &shared_state.last_activity) {
+        (ActivityState::Stopped, None) => return ("no activity", ArrayString::new(), lines),
+        (ActivityState::Stopped, Some(last)) => (
+            "last activity",
+            last.distance_m,
+            last.elapsed_s as u64,
+            last.moving_s as u64,
+            last.average_speed_mm_s()
+        ),
+        (state, _) => {
+            // The monotonic only reads zero before init, and this is only drawn after
+            let now_ms = monotonic::millis(crate::app::monotonics::now());
+            let speed = activity.speed_mm_s();
+            let _ = write!(lines[1], "now {} {}", rec::format_pace(speed, units), rec::format_speed(speed, units));
+            (
+                if state == ActivityState::Paused { "paused" } else { "recording" },
+                activity.distance_m(),
+                activity.elapsed_ms(now_ms) / 1000,
+                activity.moving_ms() / 1000,
+                activity.average_speed_mm_s()
+            )
+        }
+    };
+
+    let _ = write!(lines[0], "dist {}", geo::format_distance(distance_m, units));
+    let _ = write!(lines[2], "avg {} {}", rec::format_pace(average, units), rec::format_speed(average, units));
+    let _ = write!(lines[3], "moving {}", rec::format_duration(moving_s));
+    (title, rec::format_duration(elapsed_s), lines)
+}
@@ -0,0 +1,161 @@
+//! Analog watch face.
+//!
+//! Drawing the whole dial every second would be wasteful, so after the first draw only the areas
+//! the hands moved out of are cleared and redrawn. The display then only has lines around the
+//! hands to send.
+
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Circle, Line, Rectangle, PrimitiveStyle}
+};
+
+use crate::trig;
+
+/// Margin between the dial and the edge of the screen
+const MARGIN: u32 = 2;
+
+/// Hand positions, in sixtieths of a turn
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Hands {
+    pub hour: u32,
+    pub minute: u32,
+    /// `None` hides the seconds hand
+    pub second: Option<u32>
+}
+
+impl Hands {
+    /// Hand positions for a time of day. The hour hand moves in steps of 12 minutes.
+    pub fn new(hour: u32, minute: u32, second: Option<u32>) -> Self {
+        Self {
+            hour: (hour % 12) * 5 + minute / 12,
+            minute,
+            second
+        }
+    }
+}
+
+/// A hand: how far out it reaches (as a fraction of the dial radius, in tenths) and how thick it is
+#[derive(Debug, Copy, Clone)]
+struct HandStyle {
+    length_tenths: i32,
+    width: u32
+}
+const HOUR_HAND: HandStyle = HandStyle { length_tenths: 5, width: 5 };
+const MINUTE_HAND: HandStyle = HandStyle { length_tenths: 8, width: 3 };
+const SECOND_HAND: HandStyle = HandStyle { length_tenths: 9, width: 1 };
+
+#[derive(Debug)]
+pub struct AnalogFace {
+    /// What was last drawn, or `None` if the whole face needs to be drawn
+    drawn: Option<Hands>
+}
+
+impl AnalogFace {
+    pub fn new() -> Self {
+        Self {
+            drawn: None
+        }
+    }
+
+    /// Forget what was drawn, so the next draw redraws the whole face. Needed whenever something
+    /// else has drawn over the screen.
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    /// Draw the face with the hands at the given positions
+    pub fn draw<D: DrawTarget<Color = BinaryColor>>(&mut self, target: &mut D, hands: Hands) {
+        let dial = Dial::new(target.bounding_box());
+
+        match self.drawn {
+            Some(drawn) if drawn == hands => return,
+            Some(drawn) => {
+                // Clear where the old hands were, and put back the bits of the dial underneath
+                for (old, new, style) in [
+                    (Some(drawn.hour), Some(hands.hour), HOUR_HAND),
+                    (Some(drawn.minute), Some(hands.minute), MINUTE_HAND),
+                    (drawn.second, hands.second, SECOND_HAND)
+                ] {
+                    if let Some(old) = old.filter(|&old| Some(old) != new) {
+                        let area = dial.hand(old, style).bounding_box().offset(1);
+                        let mut clipped = target.clipped(&area);
+                        let _ = clipped.clear(BinaryColor::Off);
+                        dial.draw_ticks(&mut clipped);
+                    }
+                }
+            }
+            None => {
+                let _ = target.clear(BinaryColor::Off);
+                dial.draw_ticks(target);
+            }
+        }
+
+        // Hands can overlap the cleared areas even if they didn't move, so always draw all of them.
+        // Lines that end up the same aren't sent to the display anyway.
+        dial.draw_hands(target, &hands);
+        self.drawn = Some(hands);
+    }
+}
+
+/// Geometry of the dial
+#[derive(Debug, Copy, Clone)]
+struct Dial {
+    center: Point,
+    radius: i32
+}
+
+impl Dial {
+    /// The largest dial that fits in `area`
+    fn new(area: Rectangle) -> Self {
+        let diameter = area.size.width.min(area.size.height) - 2 * MARGIN;
+        Self {
+            center: area.center(),
+            radius: diameter as i32 / 2
+        }
+    }
+
+    /// Point `radius` out from the center at `angle` sixtieths of a turn
+    fn point(&self, angle: u32, radius: i32) -> Point {
+        let (x, y) = trig::polar(angle * 6, radius);
+        self.center + Point::new(x, y)
+    }
+
+    fn hand(&self, angle: u32, style: HandStyle) -> impl Dimensions + Drawable<Color = BinaryColor> {
+        let tip = self.point(angle, self.radius * style.length_tenths / 10);
+        Line::new(self.center, tip).into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, style.width))
+    }
+
+    /// Tick marks around the edge, longer and thicker at the hours
+    fn draw_ticks<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        for angle in 0..60 {
+            let (length, width) = if angle % 15 == 0 {
+                (10, 3)
+            }
+            else if angle % 5 == 0 {
+                (7, 2)
+            }
+            else {
+                (3, 1)
+            };
+            let outer = self.point(angle, self.radius);
+            let inner = self.point(angle, self.radius - length);
+            // Cannot error
+            let _ = Line::new(inner, outer)
+                .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, width))
+                .draw(target);
+        }
+    }
+
+    fn draw_hands<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D, hands: &Hands) {
+        let _ = self.hand(hands.hour, HOUR_HAND).draw(target);
+        let _ = self.hand(hands.minute, MINUTE_HAND).draw(target);
+        if let Some(second) = hands.second {
+            let _ = self.hand(second, SECOND_HAND).draw(target);
+        }
+        // Hub over where the hands meet
+        let _ = Circle::with_center(self.center, 7)
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(target);
+    }
+}
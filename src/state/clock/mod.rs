@@ -0,0 +1,166 @@
+//! The clock state.
+
+use embedded_graphics::{
+    self as gfx,
+    prelude::*,
+    mono_font::MonoTextStyle,
+    pixelcolor::BinaryColor,
+    primitives::PrimitiveStyle,
+    text::{Text, Alignment, Baseline}
+};
+use chrono::prelude::*;
+use core::fmt::Write;
+
+use crate::state::{self, UiMode, SharedState, Resources};
+use crate::state::settings::ClockFace;
+use crate::peripherals::battery::BatteryLevel;
+
+mod analog;
+
+use analog::{AnalogFace, Hands};
+use crate::widgets::seven_segment::SevenSegment;
+
+/// Margin around the large face
+const MARGIN: i32 = 4;
+/// Space between large digits
+const DIGIT_GAP: u32 = 4;
+/// Top of the large digits, leaving room for the status bar
+const DIGITS_TOP: i32 = 18;
+/// Width of the seconds digits on the large face
+const SECONDS_WIDTH: u32 = 10;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// Built when drawing rather than stored, since fonts aren't `Sync` and so can't be sent between
+// RTIC tasks
+fn text_style() -> MonoTextStyle<'static, BinaryColor> {
+    MonoTextStyle::new(&gfx::mono_font::iso_8859_1::FONT_4X6, BinaryColor::On)
+}
+fn date_style() -> MonoTextStyle<'static, BinaryColor> {
+    MonoTextStyle::new(&gfx::mono_font::iso_8859_1::FONT_6X10, BinaryColor::On)
+}
+
+#[derive(Debug)]
+pub struct ClockMode {
+    /// Keeps track of what the analog face last drew, so it can redraw only what changed
+    analog: AnalogFace
+}
+
+impl ClockMode {
+    pub fn new() -> Self {
+        Self {
+            analog: AnalogFace::new()
+        }
+    }
+
+    /// Redraw everything on the next draw, since something else drew over the screen
+    pub fn invalidate(&mut self) {
+        self.analog.invalidate();
+    }
+
+    pub fn update(&mut self, _resources: &mut Resources, _shared_state: &mut SharedState) -> Option<UiMode> {
+        // Stay in this state forever
+        None
+    }
+
+    pub fn draw(&mut self, resources: &mut Resources, shared_state: &SharedState) {
+        // The RTC keeps UTC, show local time
+        let offset = chrono::Duration::minutes(shared_state.settings.utc_offset_min as i64);
+        let now = resources.rtc.now() + offset;
+        // When the battery is low the display is only refreshed every few seconds, so leave out seconds
+        let low_battery = shared_state.battery.is_some_and(|b| b.level != BatteryLevel::Ok);
+
+        let face = shared_state.settings.face;
+        if face == ClockFace::Analog {
+            let time = now.time();
+            let second = if low_battery { None } else { Some(time.second()) };
+            let area = state::content_area(&resources.display);
+            let mut target = resources.display.cropped(&area);
+            self.analog.draw(&mut target, Hands::new(time.hour(), time.minute(), second));
+        }
+        else {
+            // The digital faces redraw everything under the status bar, so the analog face has to
+            // start over if it's switched back to
+            self.analog.invalidate();
+            let _ = state::content_area(&resources.display)
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+                .draw(&mut resources.display);
+            match face {
+                ClockFace::Large => draw_large(resources, shared_state, now, low_battery),
+                _ => draw_small(resources, shared_state, now.time(), low_battery)
+            }
+        }
+    }
+}
+
+/// Plain text time in the top left corner
+fn draw_small(resources: &mut Resources, shared_state: &SharedState, time: NaiveTime, low_battery: bool) {
+    // Create buffer for time string (should fit in 9 chars)
+    let mut time_str = arrayvec::ArrayString::<9>::new();
+    // Format time string
+    if low_battery {
+        write!(time_str, "{:02}:{:02}", time.hour(), time.minute()).unwrap();
+    }
+    else {
+        write!(time_str, "{:02}:{:02}:{:02}", time.hour(), time.minute(), time.second()).unwrap();
+    }
+    // Mark the time as unreliable if it hasn't been synced since the RTC lost power
+    if shared_state.last_sync.is_none() {
+        time_str.push('?');
+    }
+    // Draw text to framebuffer, below the status bar
+    // Cannot error
+    let top_left = state::content_area(&resources.display).top_left + Point::new(1, 1);
+    let _ = Text::with_baseline(&time_str, top_left, text_style(), Baseline::Top).draw(&mut resources.display);
+}
+
+/// Hours and minutes in large seven-segment digits across the screen, with the date underneath
+fn draw_large(resources: &mut Resources, shared_state: &SharedState, now: NaiveDateTime, low_battery: bool) {
+    let display = &mut resources.display;
+    let size = display.bounding_box().size;
+
+    // Four digits, the gaps between them and the colon (which is as wide as a segment is thick,
+    // so a fifth of a digit) fill the width
+    let digit_width = (size.width - 2 * MARGIN as u32 - 4 * DIGIT_GAP) * 5 / 21;
+    let digits = SevenSegment::with_width(digit_width);
+
+    let time = now.time();
+    let x = digits.draw_number(display, Point::new(MARGIN, DIGITS_TOP), time.hour(), DIGIT_GAP);
+    let x = x + DIGIT_GAP as i32;
+    digits.draw_colon(display, Point::new(x, DIGITS_TOP));
+    let x = x + (digits.colon_width() + DIGIT_GAP) as i32;
+    digits.draw_number(display, Point::new(x, DIGITS_TOP), time.minute(), DIGIT_GAP);
+
+    let center = size.width as i32 / 2;
+    let below_digits = DIGITS_TOP + digits.size.height as i32;
+
+    // Date, e.g. "Sat 18 Oct 2026"
+    let date = now.date();
+    let mut date_str = arrayvec::ArrayString::<16>::new();
+    write!(
+        date_str, "{} {} {} {}",
+        WEEKDAYS[date.weekday().num_days_from_monday() as usize],
+        date.day(),
+        MONTHS[date.month0() as usize],
+        date.year()
+    ).unwrap();
+    let _ = Text::with_alignment(&date_str, Point::new(center, below_digits + 16), date_style(), Alignment::Center)
+        .draw(display);
+
+    // Mark the time as unreliable if it hasn't been synced since the RTC lost power
+    if shared_state.last_sync.is_none() {
+        let _ = Text::with_alignment("NO SYNC", Point::new(center, below_digits + 30), date_style(), Alignment::Center)
+            .draw(display);
+    }
+
+    // Small seconds in the bottom right corner
+    if !low_battery {
+        let seconds = SevenSegment::with_width(SECONDS_WIDTH);
+        let top_left = Point::new(
+            size.width as i32 - MARGIN - 2 * SECONDS_WIDTH as i32 - 2,
+            size.height as i32 - MARGIN - seconds.size.height as i32
+        );
+        seconds.draw_number(display, top_left, time.second(), 2);
+    }
+}
@@ -0,0 +1,121 @@
+//! The diagnostics screen, shown after an unexpected reset.
+
+use embedded_graphics::{
+    self as gfx,
+    prelude::*,
+    mono_font::MonoTextStyle,
+    pixelcolor::BinaryColor,
+    text::{Text, Baseline}
+};
+use chrono::prelude::*;
+use core::fmt::Write;
+
+use crate::state::{UiMode, SharedState, Resources};
+
+/// How many updates to show the screen for before going back
+const SHOW_UPDATES: u8 = 10;
+/// Characters that fit on a line
+const LINE_CHARS: usize = 42;
+/// Height of a line of text
+#[cfg(feature = "log-ring")]
+const LINE_HEIGHT: i32 = 6;
+/// Number of recent log lines to show
+#[cfg(feature = "log-ring")]
+const LOG_LINES: usize = 12;
+
+// Built when drawing rather than stored, since fonts aren't `Sync` and so can't be sent between
+// RTIC tasks
+fn text_style() -> MonoTextStyle<'static, BinaryColor> {
+    MonoTextStyle::new(&gfx::mono_font::iso_8859_1::FONT_4X6, BinaryColor::On)
+}
+
+#[derive(Debug)]
+pub struct DiagnosticsMode {
+    /// ID of the mode to go back to
+    return_to: u8,
+    /// Updates left before going back
+    remaining: u8
+}
+
+impl DiagnosticsMode {
+    pub fn new(return_to: u8) -> Self {
+        Self {
+            return_to,
+            remaining: SHOW_UPDATES
+        }
+    }
+
+    /// ID of the mode that will be shown after this one
+    pub fn return_to(&self) -> u8 {
+        self.return_to
+    }
+
+    pub fn update(&mut self, _resources: &mut Resources, _shared_state: &mut SharedState) -> Option<UiMode> {
+        self.remaining = self.remaining.saturating_sub(1);
+        if self.remaining == 0 {
+            Some(UiMode::from_id(self.return_to).unwrap_or_default())
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn draw(&self, resources: &mut Resources, shared_state: &SharedState) {
+        resources.display.clear();
+
+        // Lines are short enough to always fit, so formatting can't fail
+        let mut text = arrayvec::ArrayString::<320>::new();
+        let _ = writeln!(text, "reset: {}", shared_state.reset_reason.name());
+        let _ = match shared_state.battery {
+            Some(b) => writeln!(text, "battery: {} mV {}%", b.millivolts, b.percent),
+            None => writeln!(text, "battery: ?")
+        };
+        let _ = match shared_state.last_sync {
+            Some(t) => writeln!(
+                text, "synced: {:04}-{:02}-{:02} {:02}:{:02}",
+                t.year(), t.month(), t.day(), t.hour(), t.minute()
+            ),
+            None => writeln!(text, "synced: never")
+        };
+        let _ = writeln!(text, "firmware: {}", env!("CARGO_PKG_VERSION"));
+
+        if let Some(crash) = &shared_state.crash {
+            let _ = writeln!(
+                text, "{}panic: {}:{}",
+                if shared_state.crash_is_new { "" } else { "last " },
+                crash.file, crash.line
+            );
+            // Wrap the message, since it's longer than a line
+            let mut rest = crash.message.as_str();
+            while !rest.is_empty() {
+                let mut split = rest.len().min(LINE_CHARS);
+                while !rest.is_char_boundary(split) {
+                    split -= 1;
+                }
+                let _ = writeln!(text, "{}", &rest[..split]);
+                rest = &rest[split..];
+            }
+            let _ = write!(text, "bt:");
+            for address in crash.backtrace.iter().filter(|&&a| a != 0) {
+                let _ = write!(text, " {:08x}", address);
+            }
+        }
+
+        // Cannot error
+        let _ = Text::with_baseline(&text, Point::new(1, 1), text_style(), Baseline::Top)
+            .draw(&mut resources.display);
+
+        // Show recent log output below
+        #[cfg(feature = "log-ring")]
+        {
+            let mut lines = [arrayvec::ArrayString::<LINE_CHARS>::new(); LOG_LINES];
+            let count = crate::LOGGER.ring().last_lines(&mut lines);
+            let top = 1 + (text.lines().count() as i32 + 1) * LINE_HEIGHT;
+            for (i, line) in lines[..count].iter().enumerate() {
+                let _ = Text::with_baseline(line, Point::new(1, top + i as i32 * LINE_HEIGHT), text_style(), Baseline::Top)
+                    .draw(&mut resources.display);
+            }
+        }
+
+    }
+}
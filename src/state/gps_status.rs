@@ -0,0 +1,138 @@
+//! GPS status screen, for debugging reception.
+
+use core::fmt::Write;
+
+use arrayvec::ArrayString;
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Rectangle, PrimitiveStyle}
+};
+use chrono::prelude::*;
+
+use crate::state::{self, UiMode, SharedState, Resources};
+use crate::peripherals::monotonic::TIMER_HZ;
+use crate::widgets::{Dirty, Font, Label};
+
+/// Characters that fit on a line
+const LINE_CHARS: usize = 28;
+/// Number of lines shown
+const LINES: usize = 10;
+/// Height of a line of text, with a gap between lines
+const LINE_HEIGHT: u32 = 12;
+
+type Line = Label<LINE_CHARS>;
+
+#[derive(Debug)]
+pub struct GpsStatusMode {
+    lines: [Dirty<Line>; LINES],
+    /// Whether the screen has been cleared since something else drew over it
+    cleared: bool
+}
+
+impl GpsStatusMode {
+    pub fn new() -> Self {
+        Self {
+            lines: core::array::from_fn(|_| Dirty::new(Line::new("", Font::Medium))),
+            cleared: false
+        }
+    }
+
+    /// Redraw everything on the next draw, since something else drew over the screen
+    pub fn invalidate(&mut self) {
+        self.cleared = false;
+    }
+
+    pub fn update(&mut self, _resources: &mut Resources, _shared_state: &mut SharedState) -> Option<UiMode> {
+        None
+    }
+
+    pub fn draw(&mut self, resources: &mut Resources, shared_state: &SharedState) {
+        let display = &mut resources.display;
+        let area = state::content_area(display);
+        if !self.cleared {
+            let _ = area.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off)).draw(display);
+            for line in &mut self.lines {
+                line.invalidate();
+            }
+            self.cleared = true;
+        }
+
+        // Only lines that changed are redrawn
+        let texts = lines(shared_state);
+        for (i, (line, text)) in self.lines.iter_mut().zip(&texts).enumerate() {
+            line.update(|label| label.set_text(text));
+            let top_left = area.top_left + Point::new(1, (i as u32 * LINE_HEIGHT) as i32);
+            line.draw(display, Rectangle::new(top_left, Size::new(area.size.width - 1, LINE_HEIGHT)));
+        }
+    }
+}
+
+/// The text of each line. Lines are short enough that formatting can't fail.
+fn lines(shared_state: &SharedState) -> [ArrayString<LINE_CHARS>; LINES] {
+    let gps = &shared_state.gps;
+    let mut lines = [ArrayString::new(); LINES];
+
+    let _ = write!(lines[0], "fix: {:?} {:?}", gps.fix_type, gps.fix_mode);
+    let _ = match gps.satellites {
+        Some(n) => write!(lines[1], "sats: {}", n),
+        None => write!(lines[1], "sats: - (no data)")
+    };
+    let _ = write!(lines[2], "HDOP ");
+    write_dop(&mut lines[2], gps.hdop);
+    let _ = write!(lines[2], "  PDOP ");
+    write_dop(&mut lines[2], gps.pdop);
+
+    for (i, (name, coord)) in [("lat", gps.latitude), ("lon", gps.longitude)].into_iter().enumerate() {
+        let line = &mut lines[3 + i];
+        let _ = write!(line, "{}: ", name);
+        let _ = match coord {
+            Some(c) => write_signed(line, c.micro_degrees(), 1_000_000),
+            None => write!(line, "-")
+        };
+    }
+    let _ = write!(lines[5], "alt: ");
+    let _ = match gps.altitude_dm {
+        Some(alt) => write_signed(&mut lines[5], alt, 10).and_then(|_| write!(lines[5], " m")),
+        None => write!(lines[5], "-")
+    };
+
+    let _ = match gps.time {
+        Some(t) => write!(lines[6], "UTC: {:02}:{:02}:{:02}", t.hour(), t.minute(), t.second()),
+        None => write!(lines[6], "UTC: -")
+    };
+    if let Some(d) = gps.date {
+        let _ = write!(lines[6], " {:04}-{:02}-{:02}", d.year(), d.month(), d.day());
+    }
+
+    // The monotonic only reads zero before init, and this is only drawn after
+    let now = crate::app::monotonics::now();
+    let _ = match gps.last_fix.and_then(|t| now.checked_duration_since(t)) {
+        Some(age) => write!(lines[7], "last fix: {} s ago", age.ticks() / TIMER_HZ as u64),
+        None => write!(lines[7], "last fix: never")
+    };
+
+    let e = &gps.errors;
+    let _ = write!(lines[8], "UART: F{} N{} O{} P{}", e.framing, e.noise, e.overrun, e.parity);
+    let _ = write!(lines[9], "NMEA: chk {} bad {}", e.checksum, e.invalid);
+
+    lines
+}
+
+/// Dilution of precision in tenths, or "-" if unknown
+fn write_dop(line: &mut ArrayString<LINE_CHARS>, dop: u8) {
+    let _ = if dop == u8::MAX {
+        write!(line, "-")
+    }
+    else {
+        write!(line, "{}.{}", dop / 10, dop % 10)
+    };
+}
+
+/// Fixed-point number with `scale` as one, e.g. 1234 with a scale of 100 is "12.34"
+fn write_signed(line: &mut ArrayString<LINE_CHARS>, value: i32, scale: u32) -> core::fmt::Result {
+    let sign = if value < 0 { "-" } else { "" };
+    let abs = value.unsigned_abs();
+    let decimals = scale.ilog10() as usize;
+    write!(line, "{}{}.{:0width$}", sign, abs / scale, abs % scale, width = decimals)
+}
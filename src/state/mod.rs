@@ -1,78 +1,299 @@
 //! State machine stuff
 
-use core::fmt::Formatter;
+use core::fmt::{Formatter, Write};
 
 use stm32l0xx_hal as hal;
+use chrono::{DateTime, NaiveDateTime};
+use embedded_graphics::{
+    self as gfx,
+    prelude::*,
+    mono_font::MonoTextStyle,
+    pixelcolor::BinaryColor,
+    primitives::{Rectangle, PrimitiveStyle},
+    text::{Text, Baseline}
+};
 
+use crate::error::{self, MainError};
+use crate::util::Truncating;
+use crate::widgets::{Dirty, StatusBar};
+
+use crate::nmea::NmeaSentence;
+use crate::track::Track;
+use crate::activity::{Activity, ActivityState, ActivitySummary};
+use crate::peripherals::{
+    battery::BatteryStatus,
+    gps::GpsInfo,
+    monotonic::{self, Instant, Duration},
+    reset::ResetReason,
+    crash::CrashReport,
+    rtc::{self, BackupState}
+};
+
+pub mod activity;
 pub mod clock;
+pub mod diagnostics;
+pub mod gps_status;
+pub mod navigate;
+pub mod sky_plot;
+pub mod track_back;
+mod status;
+
+pub use gps_watch_core::{settings, waypoints};
+
+/// How many redraws to show an error banner for
+const BANNER_DRAWS: u8 = 5;
+/// Height of the error banner
+const BANNER_HEIGHT: u32 = 8;
+/// How often to set the RTC from the GPS time, while it's getting fixes
+const TIME_SYNC_INTERVAL_MIN: i64 = 60;
 
 /// State shared by the different UI modes
 #[derive(Debug)]
 pub struct SharedState {
-
+    /// User settings, saved to EEPROM whenever they change
+    pub settings: settings::Settings,
+    /// Why the MCU was last reset
+    pub reset_reason: ResetReason,
+    /// The last saved crash report, if any
+    pub crash: Option<CrashReport>,
+    /// Whether the crash report is from the last boot, and hasn't been shown before
+    pub crash_is_new: bool,
+    /// Last battery measurement, if there's been one yet
+    pub battery: Option<BatteryStatus>,
+    /// When the time was last set from GPS, or `None` if the clock hasn't been synced since it
+    /// lost power
+    pub last_sync: Option<NaiveDateTime>,
+    /// What the GPS last reported
+    pub gps: GpsInfo,
+    /// Whether a track or activity is being recorded
+    pub recording: bool,
+    /// Trail recorded while `recording` is set, for finding the way back
+    pub track: Track,
+    /// The activity being recorded, or the last one if it's been stopped since boot
+    pub activity: Activity,
+    /// Summary of the last finished activity. Saved to EEPROM whenever it changes.
+    pub last_activity: Option<ActivitySummary>,
+    /// Saved waypoints, by slot. Saved to EEPROM whenever they change.
+    pub waypoints: [Option<waypoints::Waypoint>; waypoints::WAYPOINT_COUNT]
 }
 
 impl SharedState {
-    pub fn new() -> Self {
+    pub fn new(settings: settings::Settings, reset_reason: ResetReason, crash: Option<(CrashReport, bool)>) -> Self {
+        let (crash, crash_is_new) = match crash {
+            Some((report, new)) => (Some(report), new),
+            None => (None, false)
+        };
         Self {
+            settings,
+            reset_reason,
+            crash,
+            crash_is_new,
+            battery: None,
+            last_sync: None,
+            gps: GpsInfo::default(),
+            recording: false,
+            track: Track::default(),
+            activity: Activity::default(),
+            last_activity: None,
+            waypoints: [None; waypoints::WAYPOINT_COUNT]
+        }
+    }
+
+    /// Save the current position as a waypoint in the first free slot, named after the slot.
+    /// Returns the slot, or `None` if there's no fix or every slot is taken.
+    #[allow(dead_code)] // until there are buttons to call it from
+    pub fn mark_waypoint(&mut self) -> Option<usize> {
+        if !self.gps.has_fix() {
+            log::warn!("can't mark a waypoint without a fix");
+            return None;
+        }
+        let position = self.gps.position()?;
+        let Some(slot) = self.waypoints.iter().position(Option::is_none) else {
+            log::warn!("no free waypoint slots");
+            return None;
+        };
+
+        let mut name = arrayvec::ArrayString::new();
+        // "WP16" at most, so this can't fail
+        let _ = write!(name, "WP{:02}", slot + 1);
+        log::info!("marked waypoint {} at {:?}", name, position);
+        self.waypoints[slot] = Some(waypoints::Waypoint { name, position });
+        Some(slot)
+    }
+
+    /// Pick the next UI mode, wrapping around to the clock. Takes effect on the next update, and
+    /// is saved with the settings.
+    #[allow(dead_code)] // until there are buttons to call it from
+    pub fn next_mode(&mut self) {
+        self.settings.mode = (self.settings.mode % UiMode::PICKABLE + 1) % UiMode::PICKABLE;
+    }
+}
+
+/// Activity controls. `now` is the monotonic time.
+#[allow(dead_code)] // until there are buttons to call these from
+impl SharedState {
+    /// Start recording a new activity, along with a new track
+    pub fn start_activity(&mut self, now: Instant) {
+        log::info!("activity started");
+        self.activity.start(monotonic::millis(now));
+        self.track.clear();
+        self.recording = true;
+    }
+
+    pub fn pause_activity(&mut self, now: Instant) {
+        log::info!("activity paused");
+        self.activity.pause(monotonic::millis(now));
+        self.recording = false;
+    }
+
+    pub fn resume_activity(&mut self, now: Instant) {
+        if self.activity.state() == ActivityState::Paused {
+            log::info!("activity resumed");
+            self.activity.resume(monotonic::millis(now));
+            self.recording = true;
+        }
+    }
 
+    /// Stop the activity, keeping its summary. `ended` is the current time from the RTC.
+    pub fn stop_activity(&mut self, now: Instant, ended: NaiveDateTime) {
+        if let Some(summary) = self.activity.stop(monotonic::millis(now), ended.and_utc().timestamp() as u32) {
+            log::info!("activity stopped: {:?}", summary);
+            self.last_activity = Some(summary);
         }
+        self.recording = false;
     }
 }
 
+/// The display, on SPI1
+//todo: make generic over gfx::DrawTarget
+pub type Display = crate::peripherals::display::SharpLcd<
+    hal::spi::Spi<
+        hal::pac::SPI1,
+        (
+            hal::gpio::gpioa::PA5<hal::gpio::Analog>,
+            hal::gpio::gpioa::PA6<hal::gpio::Analog>,
+            hal::gpio::gpioa::PA7<hal::gpio::Analog>
+        )
+    >,
+    hal::gpio::gpioa::PA4<hal::gpio::Output<hal::gpio::PushPull>>
+>;
+
 /// Resources shared by the different UI modes
 pub struct Resources {
     pub rtc: hal::rtc::Rtc,
-    //todo: make generic over gfx::DrawTarget
-    pub display: crate::peripherals::display::SharpLcd<
-        hal::spi::Spi<
-            hal::pac::SPI1,
-            (
-                hal::gpio::gpioa::PA5<hal::gpio::Analog>,
-                hal::gpio::gpioa::PA6<hal::gpio::Analog>,
-                hal::gpio::gpioa::PA7<hal::gpio::Analog>
-            )
-        >,
-        hal::gpio::gpioa::PA4<hal::gpio::Output<hal::gpio::PushPull>>
-    >
+    pub settings_store: crate::peripherals::SettingsStore,
+    pub display: Display
 }
 impl core::fmt::Debug for Resources {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Resources")
             .field("display", &self.display)
+            .field("settings_store", &self.settings_store)
             .finish_non_exhaustive() // since some peripherals don't have Debug
     }
 }
 
 /// The individual UI modes, such as clock, alarms, etc.
 #[derive(Debug)]
-pub enum UiMode<'a> {
-    Clock(clock::ClockMode<'a>)
+pub enum UiMode {
+    Clock(clock::ClockMode),
+    GpsStatus(gps_status::GpsStatusMode),
+    SkyPlot(sky_plot::SkyPlotMode),
+    Navigate(navigate::NavigateMode),
+    TrackBack(track_back::TrackBackMode),
+    Activity(activity::ActivityMode),
+    Diagnostics(diagnostics::DiagnosticsMode)
 }
-impl<'a> Default for UiMode<'a> {
+impl Default for UiMode {
     fn default() -> Self {
         Self::Clock(clock::ClockMode::new())
     }
 }
 
-impl<'a> UiMode<'a> {
+impl UiMode {
+    /// Number of modes that can be picked, with IDs counting up from 0
+    pub const PICKABLE: u8 = 6;
+
+    /// ID to save the mode in the RTC backup registers and settings with
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Clock(_) => 0,
+            Self::GpsStatus(_) => 1,
+            Self::SkyPlot(_) => 2,
+            Self::Navigate(_) => 3,
+            Self::TrackBack(_) => 4,
+            Self::Activity(_) => 5,
+            // Transient, so resume whatever it would have gone back to
+            Self::Diagnostics(x) => x.return_to()
+        }
+    }
+
+    /// Recreate a mode from its ID, in its initial state
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Clock(clock::ClockMode::new())),
+            1 => Some(Self::GpsStatus(gps_status::GpsStatusMode::new())),
+            2 => Some(Self::SkyPlot(sky_plot::SkyPlotMode::new())),
+            3 => Some(Self::Navigate(navigate::NavigateMode::new())),
+            4 => Some(Self::TrackBack(track_back::TrackBackMode::new())),
+            5 => Some(Self::Activity(activity::ActivityMode::new())),
+            _ => None
+        }
+    }
+
     /// Wrapper function to dispatch to the current mode's `update()` function
     pub fn update(&mut self, resources: &mut Resources, shared_state: &mut SharedState) -> Option<Self> {
         match self {
-            Self::Clock(x) => x.update(resources, shared_state)
+            Self::Clock(x) => x.update(resources, shared_state),
+            Self::GpsStatus(x) => x.update(resources, shared_state),
+            Self::SkyPlot(x) => x.update(resources, shared_state),
+            Self::Navigate(x) => x.update(resources, shared_state),
+            Self::TrackBack(x) => x.update(resources, shared_state),
+            Self::Activity(x) => x.update(resources, shared_state),
+            Self::Diagnostics(x) => x.update(resources, shared_state)
         }
     }
 
     /// Wrapper function to dispatch to the current mode's `draw()` function
-    pub fn draw(&self, resources: &mut Resources, shared_state: &SharedState) {
+    pub fn draw(&mut self, resources: &mut Resources, shared_state: &SharedState) {
         match self {
-            Self::Clock(x) => x.draw(resources, shared_state)
+            Self::Clock(x) => x.draw(resources, shared_state),
+            Self::GpsStatus(x) => x.draw(resources, shared_state),
+            Self::SkyPlot(x) => x.draw(resources, shared_state),
+            Self::Navigate(x) => x.draw(resources, shared_state),
+            Self::TrackBack(x) => x.draw(resources, shared_state),
+            Self::Activity(x) => x.draw(resources, shared_state),
+            Self::Diagnostics(x) => x.draw(resources, shared_state)
+        }
+    }
+
+    /// Whether the mode leaves the top of the screen free for the status bar
+    pub fn has_status_bar(&self) -> bool {
+        match self {
+            Self::Clock(_) | Self::GpsStatus(_) | Self::SkyPlot(_) | Self::Navigate(_) | Self::TrackBack(_)
+                | Self::Activity(_) => true,
+            Self::Diagnostics(_) => false
+        }
+    }
+
+    /// Tell the current mode that something else drew over the screen, so it needs to redraw
+    /// everything next time
+    pub fn invalidate(&mut self) {
+        match self {
+            Self::Clock(x) => x.invalidate(),
+            Self::GpsStatus(x) => x.invalidate(),
+            Self::SkyPlot(x) => x.invalidate(),
+            Self::Navigate(x) => x.invalidate(),
+            Self::TrackBack(x) => x.invalidate(),
+            Self::Activity(x) => x.invalidate(),
+            // Redrawn in full every time
+            Self::Diagnostics(_) => ()
         }
     }
 }
 
 #[derive(Debug)]
-pub struct State<'a> {
+pub struct State {
     /// Shared resources, such as hardware peripherals
     resources: Resources,
 
@@ -80,29 +301,219 @@ pub struct State<'a> {
     shared_state: SharedState,
 
     /// The current UI state
-    mode: UiMode<'a>
+    mode: UiMode,
+
+    /// Error being shown in a banner, and how many more redraws to show it for
+    banner: Option<(MainError, u8)>,
+
+    /// Status bar along the top, if the mode allows it
+    status_bar: Dirty<StatusBar>,
+
+    /// When the last point was added to the track
+    track_logged: Option<Instant>
 }
 
-impl<'a> State<'a> {
-    pub fn new(resources: Resources, shared_state: SharedState) -> Self {
-        Self {
+impl State {
+    /// Create the state, resuming from the RTC backup registers if they survived the reset
+    pub fn new(mut resources: Resources, mut shared_state: SharedState) -> Self {
+        shared_state.waypoints = resources.settings_store.waypoints();
+        shared_state.last_activity = resources.settings_store.last_activity();
+
+        let mut mode = match rtc::read_backup(&mut resources.rtc) {
+            Some(backup) => {
+                log::info!("warm boot, resuming: {:?}", backup);
+                shared_state.last_sync = backup.last_sync
+                    .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+                    .map(|t| t.naive_utc());
+                UiMode::from_id(backup.mode).unwrap_or_default()
+            }
+            None => {
+                log::info!("cold boot, time is not valid until synced");
+                UiMode::default()
+            }
+        };
+
+        // Show why if the reset was unexpected
+        if shared_state.reset_reason.is_abnormal() || shared_state.crash_is_new {
+            log::warn!("unexpected reset: {:?}", shared_state.reset_reason);
+            mode = UiMode::Diagnostics(diagnostics::DiagnosticsMode::new(mode.id()));
+        }
+
+        let mut state = Self {
             resources,
             shared_state,
-            mode: UiMode::default()
-        }
+            mode,
+            banner: None,
+            status_bar: Dirty::new(StatusBar::new()),
+            track_logged: None
+        };
+        state.save_backup();
+        state
     }
 
     /// Update the current state. Should be called periodically.
     pub fn update(&mut self) {
+        // Switch to the mode picked in the settings. Diagnostics is left alone, since it goes back
+        // to a mode by itself, and unknown IDs (e.g. from newer firmware) are ignored.
+        let picked = self.shared_state.settings.mode;
+        let selected = match &self.mode {
+            UiMode::Diagnostics(_) => None,
+            mode if mode.id() == picked => None,
+            _ => UiMode::from_id(picked)
+        };
         // If the update switches state, switch to that state otherwise do nothing
-        match self.mode.update(&mut self.resources, &mut self.shared_state) {
-            Some(mode) => self.mode = mode,
-            None => ()
+        if let Some(mode) = selected.or_else(|| self.mode.update(&mut self.resources, &mut self.shared_state)) {
+            self.mode = mode;
+            // The new mode may have drawn over it
+            self.status_bar.invalidate();
+            self.save_backup();
+        }
+
+        // Apply and persist any settings, waypoints or activity summary the mode changed
+        let log_level = self.shared_state.settings.log_level;
+        if crate::LOGGER.level() != log_level {
+            log::info!("log level changed to {}", log_level);
+            crate::LOGGER.set_level(log_level);
+        }
+        if let Err(e) = self.resources.settings_store.save(&self.shared_state.settings) {
+            error::report(e.into());
+        }
+        if let Err(e) = self.resources.settings_store.save_waypoints(&self.shared_state.waypoints) {
+            error::report(e.into());
+        }
+        if let Err(e) = self.resources.settings_store.save_activity(&self.shared_state.last_activity) {
+            error::report(e.into());
         }
     }
 
-    /// Redraw the display
-    pub fn draw(&mut self) {
-        self.mode.draw(&mut self.resources, &self.shared_state)
+    /// Redraw the display, with the status bar on top if the mode allows it, and a banner over
+    /// the bottom if there's been an error
+    pub fn draw(&mut self) -> Result<(), MainError> {
+        self.mode.draw(&mut self.resources, &self.shared_state);
+
+        // Only redrawn when something on it changes
+        if self.mode.has_status_bar() {
+            let show_time = !matches!(self.mode, UiMode::Clock(_));
+            let now = self.resources.rtc.now();
+            self.status_bar.set(status::status_bar(now, &self.shared_state, show_time));
+            let area = Rectangle::new(
+                Point::zero(),
+                Size::new(self.resources.display.bounding_box().size.width, StatusBar::HEIGHT)
+            );
+            self.status_bar.draw(&mut self.resources.display, area);
+        }
+        else {
+            self.status_bar.invalidate();
+        }
+
+        // Newly reported errors replace the one being shown
+        if let Some(e) = error::take_latest() {
+            self.banner = Some((e, BANNER_DRAWS));
+        }
+        if let Some((e, remaining)) = &mut self.banner {
+            draw_banner(&mut self.resources.display, e);
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.banner = None;
+                // Get rid of the banner on the next draw
+                self.mode.invalidate();
+            }
+        }
+
+        self.resources.display.toggle_vcom();
+        self.resources.display.flush()?;
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Set the time from an external source (i.e. GPS), and record that the clock is synced
+    pub fn set_time(&mut self, now: NaiveDateTime) -> Result<(), MainError> {
+        self.resources.rtc.set(now)?;
+        // `set()` resets the control register, which stops the wakeup timer and disables its
+        // interrupt. Without them `update` stops running and the watchdog resets the watch.
+        rtc::start_wakeup(&mut self.resources.rtc);
+        self.shared_state.last_sync = Some(now);
+        self.save_backup();
+        Ok(())
+    }
+
+    /// Take in a sentence from the GPS, syncing the clock from it and adding to the track if
+    /// they're due, and to the activity. Returns whether the sentence had a valid fix.
+    pub fn apply_gps(&mut self, sentence: &NmeaSentence, now: Instant) -> bool {
+        let valid = self.shared_state.gps.apply(sentence, now);
+
+        // Only RMC has the speed, and a fix is reported by several sentences, so only count that
+        if let (NmeaSentence::Rmc { valid: true, .. }, Some(position)) = (sentence, self.shared_state.gps.position()) {
+            let gps = &self.shared_state.gps;
+            self.shared_state.activity.fix(position, gps.speed_cknots, monotonic::millis(now));
+        }
+
+        if valid && self.shared_state.recording {
+            let interval = Duration::secs(self.shared_state.settings.log_interval_s as u64);
+            let due = self.track_logged.is_none_or(|last| {
+                now.checked_duration_since(last).is_none_or(|d| d >= interval)
+            });
+            if let Some(position) = self.shared_state.gps.position().filter(|_| due) {
+                self.shared_state.track.push(position);
+                self.track_logged = Some(now);
+            }
+        }
+
+        // Only RMC has the date
+        if let NmeaSentence::Rmc { valid: true, date: Some(date), time: Some(time), .. } = sentence {
+            let utc = date.and_time(*time);
+            let due = self.shared_state.last_sync.is_none_or(|last| {
+                // Also resync if the clock has somehow been set into the future
+                utc < last || utc - last >= chrono::Duration::minutes(TIME_SYNC_INTERVAL_MIN)
+            });
+            if due {
+                log::info!("syncing time to GPS: {}", utc);
+                error::handle(self.set_time(utc));
+            }
+        }
+
+        valid
+    }
+
+    /// Save what's needed to resume after a reset to the RTC backup registers
+    fn save_backup(&mut self) {
+        let backup = BackupState {
+            last_sync: self.shared_state.last_sync.map(|t| t.and_utc().timestamp() as u32),
+            mode: self.mode.id()
+        };
+        rtc::write_backup(&mut self.resources.rtc, &backup);
+    }
+
+    /// Access the resources from outside the UI, e.g. from interrupt handlers
+    pub fn resources(&mut self) -> &mut Resources {
+        &mut self.resources
+    }
+
+    /// Access the shared state from outside the UI, e.g. to pass in sensor readings
+    pub fn shared_state(&mut self) -> &mut SharedState {
+        &mut self.shared_state
+    }
+}
+
+/// The area below the status bar, for modes that allow it to draw in
+pub fn content_area<D: Dimensions>(target: &D) -> Rectangle {
+    let area = target.bounding_box();
+    Rectangle::new(
+        area.top_left + Point::new(0, StatusBar::HEIGHT as i32),
+        Size::new(area.size.width, area.size.height.saturating_sub(StatusBar::HEIGHT))
+    )
+}
+
+/// Draw an error message in white on black across the bottom of the display
+fn draw_banner<D: DrawTarget<Color = BinaryColor>>(target: &mut D, e: &MainError) {
+    let area = target.bounding_box();
+    let top_left = Point::new(0, area.size.height as i32 - BANNER_HEIGHT as i32);
+    let _ = Rectangle::new(top_left, Size::new(area.size.width, BANNER_HEIGHT))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target);
+
+    // Truncated to fit the width of the screen
+    let mut text = arrayvec::ArrayString::<42>::new();
+    let _ = write!(Truncating(&mut text), "{}", e);
+    let style = MonoTextStyle::new(&gfx::mono_font::iso_8859_1::FONT_4X6, BinaryColor::Off);
+    let _ = Text::with_baseline(&text, top_left + Point::new(1, 1), style, Baseline::Top).draw(target);
+}
@@ -0,0 +1,119 @@
+//! Navigate to a saved waypoint: how far away it is, and an arrow pointing to it.
+//!
+//! While moving, the arrow is relative to the course over ground, so straight up means keep going
+//! the way you're going. When stopped the course is meaningless, so it points relative to north.
+
+use arrayvec::ArrayString;
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::PrimitiveStyle,
+    text::Alignment
+};
+
+use crate::state::{self, UiMode, SharedState, Resources};
+use crate::geo;
+use crate::peripherals::gps::GpsInfo;
+use crate::widgets::{Arrow, Dirty, Font, Label, Layout, Constraint};
+
+/// Speed over ground above which the course is trusted, in hundredths of a knot (about 0.5 m/s)
+const MOVING_SPEED_CKNOTS: u32 = 100;
+
+#[derive(Debug)]
+pub struct NavigateMode {
+    /// Slot of the waypoint being navigated to
+    target: usize,
+    name: Dirty<Label<16>>,
+    arrow: Dirty<Option<Arrow>>,
+    distance: Dirty<Label<12>>,
+    note: Dirty<Label<40>>,
+    /// Whether the screen has been cleared since something else drew over it
+    cleared: bool
+}
+
+impl NavigateMode {
+    pub fn new() -> Self {
+        Self {
+            target: 0,
+            name: Dirty::new(Label::new("", Font::Medium).aligned(Alignment::Center)),
+            arrow: Dirty::new(None),
+            distance: Dirty::new(Label::new("", Font::Large).aligned(Alignment::Center)),
+            note: Dirty::new(Label::new("", Font::Small).aligned(Alignment::Center)),
+            cleared: false
+        }
+    }
+
+    /// Redraw everything on the next draw, since something else drew over the screen
+    pub fn invalidate(&mut self) {
+        self.cleared = false;
+    }
+
+    pub fn update(&mut self, _resources: &mut Resources, shared_state: &mut SharedState) -> Option<UiMode> {
+        // Move on to another waypoint if the target's been removed
+        if shared_state.waypoints[self.target].is_none() {
+            if let Some(slot) = shared_state.waypoints.iter().position(Option::is_some) {
+                self.target = slot;
+            }
+        }
+        None
+    }
+
+    pub fn draw(&mut self, resources: &mut Resources, shared_state: &SharedState) {
+        let display = &mut resources.display;
+        let area = state::content_area(display);
+        if !self.cleared {
+            let _ = area.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off)).draw(display);
+            self.name.invalidate();
+            self.arrow.invalidate();
+            self.distance.invalidate();
+            self.note.invalidate();
+            self.cleared = true;
+        }
+
+        let (name, arrow, distance, note) = contents(self.target, shared_state);
+        self.name.update(|label| label.set_text(name));
+        self.arrow.set(arrow);
+        self.distance.update(|label| label.set_text(&distance));
+        self.note.update(|label| label.set_text(note));
+
+        let [name_area, arrow_area, distance_area, note_area] = Layout::vertical(area)
+            .spacing(2)
+            .split([
+                Constraint::Fixed(Font::Medium.height() + 2),
+                Constraint::Fill(1),
+                Constraint::Fixed(Font::Large.height()),
+                Constraint::Fixed(Font::Small.height() + 2)
+            ]);
+        self.name.draw(display, name_area);
+        self.arrow.draw(display, arrow_area);
+        self.distance.draw(display, distance_area);
+        self.note.draw(display, note_area);
+    }
+}
+
+/// The waypoint's name, the arrow, the distance and a note on what the arrow is relative to
+type Contents<'a> = (&'a str, Option<Arrow>, ArrayString<12>, &'static str);
+
+fn contents(target: usize, shared_state: &SharedState) -> Contents<'_> {
+    let gps = &shared_state.gps;
+    let Some(waypoint) = &shared_state.waypoints[target] else {
+        return ("no waypoints", None, ArrayString::new(), "");
+    };
+    let Some(here) = gps.position().filter(|_| gps.has_fix()) else {
+        return (&waypoint.name, None, ArrayString::from("--").unwrap(), "waiting for fix");
+    };
+
+    let distance = geo::format_distance(here.distance_m(&waypoint.position), shared_state.settings.units);
+    let (arrow, note) = pointer(gps, here.bearing_deg(&waypoint.position));
+    (&waypoint.name, Some(arrow), distance, note)
+}
+
+/// An arrow pointing at a bearing, relative to the course over ground if moving or north if not,
+/// along with a note saying which
+pub(super) fn pointer(gps: &GpsInfo, bearing: u32) -> (Arrow, &'static str) {
+    let moving = gps.speed_cknots.is_some_and(|s| s >= MOVING_SPEED_CKNOTS);
+    match gps.course_ddeg {
+        Some(course) if moving => (Arrow::new(bearing + 360 - course as u32 / 10), "up is direction of travel"),
+        _ => (Arrow::new(bearing), "up is north (not moving)")
+    }
+}
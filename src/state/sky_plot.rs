@@ -0,0 +1,166 @@
+//! Satellite sky plot: where the satellites in view are, how strong their signals are, and which
+//! are being used in the fix.
+
+use arrayvec::{ArrayVec, ArrayString};
+use core::fmt::Write;
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::{Circle, Line, Rectangle, PrimitiveStyle, PrimitiveStyleBuilder},
+    text::{Text, Baseline}
+};
+
+use crate::state::{self, UiMode, SharedState, Resources};
+use crate::nmea::SatelliteInView;
+use crate::peripherals::gps::MAX_SATELLITES;
+use crate::widgets::{Dirty, Font, Layout, Constraint, Widget};
+use crate::trig;
+
+/// Height of the SNR bars, including their labels
+const BARS_HEIGHT: u32 = 34;
+/// Width each SNR bar gets, including the gap to the next one
+const BAR_SLOT: u32 = 12;
+/// SNR of a full height bar, in dB-Hz. Anything above this is a very strong signal.
+const BAR_MAX_SNR: u32 = 50;
+/// Diameter of a satellite's dot on the plot
+const DOT_SIZE: u32 = 7;
+
+/// Satellites to show, and whether each is used in the fix
+type Sky = ArrayVec<(SatelliteInView, bool), MAX_SATELLITES>;
+
+#[derive(Debug)]
+pub struct SkyPlotMode {
+    plot: Dirty<SkyPlot>,
+    bars: Dirty<SnrBars>,
+    /// Whether the screen has been cleared since something else drew over it
+    cleared: bool
+}
+
+impl SkyPlotMode {
+    pub fn new() -> Self {
+        Self {
+            plot: Dirty::new(SkyPlot(Sky::new())),
+            bars: Dirty::new(SnrBars(Sky::new())),
+            cleared: false
+        }
+    }
+
+    /// Redraw everything on the next draw, since something else drew over the screen
+    pub fn invalidate(&mut self) {
+        self.cleared = false;
+    }
+
+    pub fn update(&mut self, _resources: &mut Resources, _shared_state: &mut SharedState) -> Option<UiMode> {
+        None
+    }
+
+    pub fn draw(&mut self, resources: &mut Resources, shared_state: &SharedState) {
+        let display = &mut resources.display;
+        let area = state::content_area(display);
+        if !self.cleared {
+            let _ = area.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off)).draw(display);
+            self.plot.invalidate();
+            self.bars.invalidate();
+            self.cleared = true;
+        }
+
+        // The monotonic only reads zero before init, and this is only drawn after
+        let now = crate::app::monotonics::now();
+        let mut sky: Sky = shared_state.gps.sky.iter()
+            .map(|s| (s.info, s.is_used(now)))
+            .collect();
+        sky.sort_unstable_by_key(|(info, _)| info.prn);
+
+        let [plot_area, bars_area] = Layout::vertical(area)
+            .spacing(2)
+            .split([Constraint::Fill(1), Constraint::Fixed(BARS_HEIGHT)]);
+        self.plot.set(SkyPlot(sky.clone()));
+        self.plot.draw(display, plot_area);
+        self.bars.set(SnrBars(sky));
+        self.bars.draw(display, bars_area);
+    }
+}
+
+/// Polar plot with the horizon at the edge and straight up in the middle, north at the top
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SkyPlot(Sky);
+
+impl Widget for SkyPlot {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let center = area.center();
+        // Leave room for dots on the horizon
+        let radius = (area.size.width.min(area.size.height) / 2).saturating_sub(DOT_SIZE / 2 + 1) as i32;
+        let line = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+
+        // Horizon, and 30° and 60° elevation
+        for r in [radius, radius * 2 / 3, radius / 3] {
+            // Cannot error
+            let _ = Circle::with_center(center, 2 * r as u32 + 1).into_styled(line).draw(target);
+        }
+        let _ = Line::new(center - Point::new(radius, 0), center + Point::new(radius, 0)).into_styled(line).draw(target);
+        let _ = Line::new(center - Point::new(0, radius), center + Point::new(0, radius)).into_styled(line).draw(target);
+        let style = Font::Small.style(BinaryColor::On);
+        let _ = Text::with_baseline("N", center + Point::new(2, -radius + 1), style, Baseline::Top).draw(target);
+
+        let used = PrimitiveStyle::with_fill(BinaryColor::On);
+        let unused = PrimitiveStyleBuilder::new()
+            .stroke_color(BinaryColor::On)
+            .stroke_width(1)
+            .fill_color(BinaryColor::Off)
+            .build();
+        for (info, is_used) in &self.0 {
+            // Satellites the receiver hasn't located yet can't be plotted
+            let (Some(elevation), Some(azimuth)) = (info.elevation, info.azimuth) else {
+                continue;
+            };
+            let distance = radius * (90 - elevation as i32) / 90;
+            let (x, y) = trig::polar(azimuth as u32, distance);
+            let position = center + Point::new(x, y);
+
+            let _ = Circle::with_center(position, DOT_SIZE)
+                .into_styled(if *is_used { used } else { unused })
+                .draw(target);
+            let mut label = ArrayString::<3>::new();
+            let _ = write!(label, "{}", info.prn);
+            let _ = Text::with_baseline(&label, position + Point::new(DOT_SIZE as i32 / 2 + 1, -3), style, Baseline::Top)
+                .draw(target);
+        }
+    }
+}
+
+/// A bar for each satellite's SNR, labelled with its PRN. Filled in if it's used in the fix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SnrBars(Sky);
+
+impl Widget for SnrBars {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) {
+        let area = target.bounding_box();
+        let label_height = Font::Small.height();
+        let max_height = area.size.height.saturating_sub(label_height + 1);
+        let bottom = max_height as i32;
+        let style = Font::Small.style(BinaryColor::On);
+
+        // As many as fit, in order of PRN
+        let slots = (area.size.width / BAR_SLOT) as usize;
+        for (i, (info, used)) in self.0.iter().take(slots).enumerate() {
+            let x = (i as u32 * BAR_SLOT) as i32;
+            if let Some(snr) = info.snr {
+                let height = (snr as u32).min(BAR_MAX_SNR) * max_height / BAR_MAX_SNR;
+                let bar = Rectangle::new(Point::new(x, bottom - height as i32), Size::new(BAR_SLOT - 3, height.max(1)));
+                let bar_style = if *used {
+                    PrimitiveStyle::with_fill(BinaryColor::On)
+                }
+                else {
+                    PrimitiveStyle::with_stroke(BinaryColor::On, 1)
+                };
+                // Cannot error
+                let _ = bar.into_styled(bar_style).draw(target);
+            }
+
+            let mut label = ArrayString::<3>::new();
+            let _ = write!(label, "{}", info.prn);
+            let _ = Text::with_baseline(&label, Point::new(x, bottom + 1), style, Baseline::Top).draw(target);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! The status bar shown along the top of the screen by modes that allow it.
+
+use core::fmt::Write;
+
+use embedded_graphics::prelude::*;
+use chrono::Timelike;
+
+use crate::state::SharedState;
+use crate::peripherals::battery::BatteryLevel;
+use crate::widgets::{Icon, StatusBar, StatusItem};
+
+/// Battery outlines, with 0, 1, 2 and 3 bars
+const BATTERY_ICONS: [[u8; 14]; 4] = [
+    [
+        0b11111111, 0b11100000,
+        0b10000000, 0b00100000,
+        0b10000000, 0b00110000,
+        0b10000000, 0b00110000,
+        0b10000000, 0b00110000,
+        0b10000000, 0b00100000,
+        0b11111111, 0b11100000
+    ],
+    [
+        0b11111111, 0b11100000,
+        0b10000000, 0b00100000,
+        0b10110000, 0b00110000,
+        0b10110000, 0b00110000,
+        0b10110000, 0b00110000,
+        0b10000000, 0b00100000,
+        0b11111111, 0b11100000
+    ],
+    [
+        0b11111111, 0b11100000,
+        0b10000000, 0b00100000,
+        0b10111100, 0b00110000,
+        0b10111100, 0b00110000,
+        0b10111100, 0b00110000,
+        0b10000000, 0b00100000,
+        0b11111111, 0b11100000
+    ],
+    [
+        0b11111111, 0b11100000,
+        0b10000000, 0b00100000,
+        0b10111111, 0b10110000,
+        0b10111111, 0b10110000,
+        0b10111111, 0b10110000,
+        0b10000000, 0b00100000,
+        0b11111111, 0b11100000
+    ]
+];
+const BATTERY_SIZE: Size = Size::new(12, 7);
+
+/// Satellite dish, filled in when the GPS has a fix
+const GPS_FIX_ICON: [u8; 8] = [
+    0b00000110,
+    0b00000001,
+    0b01100101,
+    0b11110000,
+    0b11111000,
+    0b01111100,
+    0b00111100,
+    0b00011000
+];
+const GPS_NO_FIX_ICON: [u8; 8] = [
+    0b00000110,
+    0b00000001,
+    0b01100101,
+    0b10010000,
+    0b10001000,
+    0b01000100,
+    0b00100100,
+    0b00011000
+];
+/// Bell, shown when an alarm is set
+const ALARM_ICON: [u8; 8] = [
+    0b00011000,
+    0b00111100,
+    0b01111110,
+    0b01111110,
+    0b01111110,
+    0b11111111,
+    0b00000000,
+    0b00011000
+];
+/// Dot, shown while recording
+const RECORDING_ICON: [u8; 7] = [
+    0b00111000,
+    0b01111100,
+    0b11111110,
+    0b11111110,
+    0b11111110,
+    0b01111100,
+    0b00111000
+];
+
+/// Build the status bar for the current state. The time is left out if `show_time` isn't set,
+/// e.g. because the mode already shows it.
+pub fn status_bar(now: chrono::NaiveDateTime, shared_state: &SharedState, show_time: bool) -> StatusBar {
+    let mut bar = StatusBar::new();
+
+    if show_time {
+        // The RTC keeps UTC, show local time
+        let offset = chrono::Duration::minutes(shared_state.settings.utc_offset_min as i64);
+        let time = (now + offset).time();
+        // Cannot overflow
+        let _ = write!(bar.text, "{:02}:{:02}", time.hour(), time.minute());
+        if shared_state.last_sync.is_none() {
+            bar.text.push('?');
+        }
+    }
+
+    // Items are added right to left
+    if let Some(battery) = shared_state.battery {
+        let bars = match battery.level {
+            BatteryLevel::Critical => 0,
+            _ => (battery.percent as usize * 3).div_ceil(100).clamp(1, 3)
+        };
+        bar.items.push(StatusItem::new(Icon::new(&BATTERY_ICONS[bars], BATTERY_SIZE)));
+    }
+
+    if let Some(satellites) = shared_state.gps.satellites {
+        let icon = if shared_state.gps.has_fix() { &GPS_FIX_ICON } else { &GPS_NO_FIX_ICON };
+        let mut item = StatusItem::new(Icon::new(icon, Size::new(8, 8)));
+        let _ = write!(item.label, "{}", satellites.min(99));
+        bar.items.push(item);
+    }
+
+    if shared_state.settings.alarms.iter().any(|a| a.enabled) {
+        bar.items.push(StatusItem::new(Icon::new(&ALARM_ICON, Size::new(8, 8))));
+    }
+
+    if shared_state.recording {
+        bar.items.push(StatusItem::new(Icon::new(&RECORDING_ICON, Size::new(7, 7))));
+    }
+
+    bar
+}
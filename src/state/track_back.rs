@@ -0,0 +1,173 @@
+//! Return to start: guide back along the recorded trail, simplified into a handful of points.
+//!
+//! The route is worked out once when the mode is entered, from the trail recorded so far, or once
+//! there is a trail if there wasn't one yet. Each point is ticked off on getting within
+//! [`PROXIMITY_M`] of it, moving on to the next.
+
+use core::fmt::Write;
+
+use arrayvec::{ArrayVec, ArrayString};
+use embedded_graphics::{
+    prelude::*,
+    pixelcolor::BinaryColor,
+    primitives::PrimitiveStyle,
+    text::Alignment
+};
+
+use crate::state::{self, UiMode, SharedState, Resources, navigate};
+use crate::geo::{self, Position};
+use crate::track::ROUTE_POINTS;
+use crate::widgets::{Arrow, Dirty, Font, Label, Layout, Constraint};
+
+/// How close to get to a route point before moving on to the next, in meters. Comfortably more
+/// than the GPS's error.
+const PROXIMITY_M: u32 = 25;
+
+#[derive(Debug)]
+pub struct TrackBackMode {
+    /// Points to go through, the start last. `None` until there's a trail to work it out from.
+    route: Option<ArrayVec<Position, ROUTE_POINTS>>,
+    /// Index of the route point being headed for
+    next: usize,
+    title: Dirty<Label<20>>,
+    arrow: Dirty<Option<Arrow>>,
+    distance: Dirty<Label<12>>,
+    remaining: Dirty<Label<24>>,
+    note: Dirty<Label<40>>,
+    /// Whether the screen has been cleared since something else drew over it
+    cleared: bool
+}
+
+impl TrackBackMode {
+    pub fn new() -> Self {
+        Self {
+            route: None,
+            next: 0,
+            title: Dirty::new(Label::new("", Font::Medium).aligned(Alignment::Center)),
+            arrow: Dirty::new(None),
+            distance: Dirty::new(Label::new("", Font::Large).aligned(Alignment::Center)),
+            remaining: Dirty::new(Label::new("", Font::Small).aligned(Alignment::Center)),
+            note: Dirty::new(Label::new("", Font::Small).aligned(Alignment::Center)),
+            cleared: false
+        }
+    }
+
+    /// Redraw everything on the next draw, since something else drew over the screen
+    pub fn invalidate(&mut self) {
+        self.cleared = false;
+    }
+
+    pub fn update(&mut self, _resources: &mut Resources, shared_state: &mut SharedState) -> Option<UiMode> {
+        // The mode can be entered before anything's been recorded, e.g. resumed after a reset, so
+        // keep trying until there's a route
+        if self.route.is_none() && !shared_state.track.points().is_empty() {
+            let route = shared_state.track.route_back();
+            log::info!("route back to start: {} points", route.len());
+            self.route = Some(route);
+        }
+        let Some(route) = &self.route else {
+            return None;
+        };
+
+        // Tick off points as they're reached
+        if let Some(here) = fix_position(shared_state) {
+            while let Some(point) = route.get(self.next) {
+                if here.distance_m(point) > PROXIMITY_M {
+                    break;
+                }
+                self.next += 1;
+                log::info!("reached route point {}/{}", self.next, route.len());
+            }
+        }
+        None
+    }
+
+    pub fn draw(&mut self, resources: &mut Resources, shared_state: &SharedState) {
+        let display = &mut resources.display;
+        let area = state::content_area(display);
+        if !self.cleared {
+            let _ = area.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off)).draw(display);
+            self.title.invalidate();
+            self.arrow.invalidate();
+            self.distance.invalidate();
+            self.remaining.invalidate();
+            self.note.invalidate();
+            self.cleared = true;
+        }
+
+        let contents = self.contents(shared_state);
+        self.title.update(|label| label.set_text(&contents.title));
+        self.arrow.set(contents.arrow);
+        self.distance.update(|label| label.set_text(&contents.distance));
+        self.remaining.update(|label| label.set_text(&contents.remaining));
+        self.note.update(|label| label.set_text(contents.note));
+
+        let [title_area, arrow_area, distance_area, remaining_area, note_area] = Layout::vertical(area)
+            .spacing(2)
+            .split([
+                Constraint::Fixed(Font::Medium.height() + 2),
+                Constraint::Fill(1),
+                Constraint::Fixed(Font::Large.height()),
+                Constraint::Fixed(Font::Small.height() + 2),
+                Constraint::Fixed(Font::Small.height() + 2)
+            ]);
+        self.title.draw(display, title_area);
+        self.arrow.draw(display, arrow_area);
+        self.distance.draw(display, distance_area);
+        self.remaining.draw(display, remaining_area);
+        self.note.draw(display, note_area);
+    }
+
+    /// What to show, given how far along the route we are. Texts are short enough that
+    /// formatting can't fail.
+    fn contents(&self, shared_state: &SharedState) -> Contents {
+        let mut contents = Contents::default();
+        let route = match &self.route {
+            Some(route) if !route.is_empty() => route,
+            _ => {
+                let _ = contents.title.write_str("no track recorded");
+                return contents;
+            }
+        };
+        let Some(next) = route.get(self.next) else {
+            let _ = contents.title.write_str("back at start");
+            return contents;
+        };
+        let _ = write!(contents.title, "to start {}/{}", self.next + 1, route.len());
+        let Some(here) = fix_position(shared_state) else {
+            let _ = contents.distance.write_str("--");
+            contents.note = "waiting for fix";
+            return contents;
+        };
+
+        let units = shared_state.settings.units;
+        let to_next = here.distance_m(next);
+        // Then along the rest of the route
+        let to_start = route[self.next..].windows(2)
+            .fold(to_next, |total, leg| total.saturating_add(leg[0].distance_m(&leg[1])));
+        contents.distance = geo::format_distance(to_next, units);
+        let _ = write!(contents.remaining, "{} to start", geo::format_distance(to_start, units));
+        let (arrow, note) = navigate::pointer(&shared_state.gps, here.bearing_deg(next));
+        contents.arrow = Some(arrow);
+        contents.note = note;
+        contents
+    }
+}
+
+/// What the mode shows
+#[derive(Debug, Default)]
+struct Contents {
+    title: ArrayString<20>,
+    arrow: Option<Arrow>,
+    /// To the next route point
+    distance: ArrayString<12>,
+    /// To the start, along the route
+    remaining: ArrayString<24>,
+    note: &'static str
+}
+
+/// Where we are, if there's a fix
+fn fix_position(shared_state: &SharedState) -> Option<Position> {
+    let gps = &shared_state.gps;
+    gps.position().filter(|_| gps.has_fix())
+}
@@ -0,0 +1,18 @@
+//! Small helpers that don't belong anywhere else
+
+use core::fmt;
+use arrayvec::ArrayString;
+
+/// Writer that keeps as much as fits and drops the rest, instead of failing like `ArrayString` does
+pub struct Truncating<'a, const N: usize>(pub &'a mut ArrayString<N>);
+
+impl<'a, const N: usize> fmt::Write for Truncating<'a, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.0.try_push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}